@@ -7,11 +7,10 @@ mod state;
 use axum::{
     error_handling::HandleErrorLayer,
     http::StatusCode,
-    routing::{any, get},
+    routing::{any, get, post},
     Router,
 };
-use baffao::oauth::OAuthHttpHandler;
-use hyper_util::{client::legacy::connect::HttpConnector, rt::TokioExecutor};
+use baffao::oauth::OAuthProviders;
 use std::time::Duration;
 use tokio::signal;
 use tower::{timeout::TimeoutLayer, BoxError, ServiceBuilder};
@@ -20,9 +19,11 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 use crate::settings::Settings;
 
+const SESSION_CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
 #[tokio::main]
 async fn main() {
-    let settings = Settings::new().unwrap();
+    let settings = Settings::new().unwrap().with_route_resources();
 
     tracing_subscriber::registry()
         .with(
@@ -32,21 +33,57 @@ async fn main() {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    let client: state::HttpClient =
-        hyper_util::client::legacy::Client::<(), ()>::builder(TokioExecutor::new())
-            .build(HttpConnector::new());
-    let oauth_http_handler =
-        OAuthHttpHandler::new(settings.oauth.clone(), settings.server.cookies.clone()).unwrap();
+    let client = state::build_http_client(settings.oauth.tls.as_ref()).unwrap();
+    let oauth_providers = OAuthProviders::new(
+        settings.oauth.clone(),
+        settings.providers.clone(),
+        settings.server.cookies.clone(),
+        settings.server.session.clone(),
+    )
+    .await
+    .unwrap();
+    let session_store = oauth_providers.session_store();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SESSION_CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            session_store.remove_expired().await;
+        }
+    });
     let app_state = state::AppState {
         client,
-        oauth_http_handler,
+        oauth_providers,
         settings: settings.clone(),
     };
 
     let app = Router::new()
         .route("/oauth/authorize", get(oauth::authorize))
-        .route("/oauth/callback", get(oauth::callback))
+        .route(
+            "/oauth/callback",
+            get(oauth::callback).post(oauth::form_callback),
+        )
+        .route("/oauth/logout", get(oauth::logout))
+        .route("/oauth/logout/callback", get(oauth::logout_callback))
+        .route("/oauth/backchannel-logout", post(oauth::backchannel_logout))
+        .route(
+            "/oauth/frontchannel-logout",
+            get(oauth::frontchannel_logout),
+        )
+        .route("/oauth/:provider/authorize", get(oauth::provider_authorize))
+        .route(
+            "/oauth/:provider/callback",
+            get(oauth::provider_callback).post(oauth::provider_form_callback),
+        )
+        .route(
+            "/oauth/:provider/backchannel-logout",
+            post(oauth::backchannel_logout),
+        )
+        .route(
+            "/oauth/:provider/frontchannel-logout",
+            get(oauth::frontchannel_logout),
+        )
         .route("/session", get(session::get_session))
+        .route("/session/userinfo", get(session::get_userinfo))
         .fallback(any(proxy::handler))
         .layer(
             ServiceBuilder::new()
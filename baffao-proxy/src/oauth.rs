@@ -1,22 +1,69 @@
+use serde::Serialize;
+
 use crate::Settings;
 
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+    error_description: String,
+}
+
 use axum::{
-    extract::{Query, State},
+    extract::{Path, Query, State},
+    http::{header::CACHE_CONTROL, Method, StatusCode},
     response::{IntoResponse, Redirect},
+    Form, Json,
 };
 use axum_extra::extract::CookieJar;
 use baffao::{
-    handlers::{oauth2_authorize, oauth2_callback, AuthorizationCallbackQuery, AuthorizationQuery},
-    oauth::OAuthHttpHandler,
+    error::build_error_redirect_url,
+    handlers::{
+        oauth2_authorize, oauth2_backchannel_logout, oauth2_callback, oauth2_frontchannel_logout,
+        oauth2_logout, oauth2_logout_callback, AuthorizationCallbackQuery, AuthorizationQuery,
+        BackchannelLogoutRequest, FrontchannelLogoutQuery, LogoutCallbackQuery,
+    },
+    oauth::OAuthProviders,
 };
 
+fn unknown_provider(jar: CookieJar, settings: &Settings) -> (CookieJar, Redirect) {
+    (
+        jar,
+        Redirect::temporary(&build_error_redirect_url(
+            &settings.server.error_url,
+            "Unknown provider",
+        )),
+    )
+}
+
 // TODO: use signed cookies
 pub async fn authorize(
     jar: CookieJar,
     query: Option<Query<AuthorizationQuery>>,
-    State(handler): State<OAuthHttpHandler>,
+    State(providers): State<OAuthProviders>,
+    State(settings): State<Settings>,
+) -> impl IntoResponse {
+    let query = query.map(|q| q.0);
+    let provider = query.as_ref().and_then(|q| q.provider.as_deref());
+    let Some(handler) = providers.get(provider) else {
+        return unknown_provider(jar, &settings);
+    };
+    let (updated_jar, _, url) = oauth2_authorize(handler, settings.server, jar, query).await;
+
+    (updated_jar, Redirect::temporary(&url.to_string()))
+}
+
+pub async fn provider_authorize(
+    jar: CookieJar,
+    Path(provider): Path<String>,
+    query: Option<Query<AuthorizationQuery>>,
+    State(providers): State<OAuthProviders>,
+    State(settings): State<Settings>,
 ) -> impl IntoResponse {
-    let (updated_jar, _, url) = oauth2_authorize(handler, jar, query.map(|q| q.0));
+    let Some(handler) = providers.get(Some(&provider)) else {
+        return unknown_provider(jar, &settings);
+    };
+    let (updated_jar, _, url) =
+        oauth2_authorize(handler, settings.server, jar, query.map(|q| q.0)).await;
 
     (updated_jar, Redirect::temporary(&url.to_string()))
 }
@@ -24,10 +71,120 @@ pub async fn authorize(
 pub async fn callback(
     jar: CookieJar,
     Query(query): Query<AuthorizationCallbackQuery>,
-    State(handler): State<OAuthHttpHandler>,
+    State(providers): State<OAuthProviders>,
+    State(settings): State<Settings>,
+) -> impl IntoResponse {
+    let Some(handler) = providers.for_callback(&jar) else {
+        return unknown_provider(jar, &settings);
+    };
+    let (updated_jar, _, url) =
+        oauth2_callback(handler, settings.server, jar, &Method::GET, query).await;
+
+    (updated_jar, Redirect::temporary(&url.to_string()))
+}
+
+pub async fn provider_callback(
+    jar: CookieJar,
+    Path(provider): Path<String>,
+    Query(query): Query<AuthorizationCallbackQuery>,
+    State(providers): State<OAuthProviders>,
+    State(settings): State<Settings>,
+) -> impl IntoResponse {
+    let Some(handler) = providers.get(Some(&provider)) else {
+        return unknown_provider(jar, &settings);
+    };
+    let (updated_jar, _, url) =
+        oauth2_callback(handler, settings.server, jar, &Method::GET, query).await;
+
+    (updated_jar, Redirect::temporary(&url.to_string()))
+}
+
+// The form post response modes are redirected with 303 to get back to a GET request
+pub async fn form_callback(
+    jar: CookieJar,
+    State(providers): State<OAuthProviders>,
+    State(settings): State<Settings>,
+    Form(query): Form<AuthorizationCallbackQuery>,
+) -> impl IntoResponse {
+    let Some(handler) = providers.for_callback(&jar) else {
+        let url = build_error_redirect_url(&settings.server.error_url, "Unknown provider");
+        return (jar, Redirect::to(&url));
+    };
+    let (updated_jar, _, url) =
+        oauth2_callback(handler, settings.server, jar, &Method::POST, query).await;
+
+    (updated_jar, Redirect::to(&url.to_string()))
+}
+
+pub async fn provider_form_callback(
+    jar: CookieJar,
+    Path(provider): Path<String>,
+    State(providers): State<OAuthProviders>,
     State(settings): State<Settings>,
+    Form(query): Form<AuthorizationCallbackQuery>,
 ) -> impl IntoResponse {
-    let (updated_jar, _, url) = oauth2_callback(handler, settings.server, jar, query).await;
+    let Some(handler) = providers.get(Some(&provider)) else {
+        let url = build_error_redirect_url(&settings.server.error_url, "Unknown provider");
+        return (jar, Redirect::to(&url));
+    };
+    let (updated_jar, _, url) =
+        oauth2_callback(handler, settings.server, jar, &Method::POST, query).await;
+
+    (updated_jar, Redirect::to(&url.to_string()))
+}
+
+pub async fn logout(jar: CookieJar, State(providers): State<OAuthProviders>) -> impl IntoResponse {
+    let handler = providers.for_session(&jar).await;
+    let (updated_jar, _, url) = oauth2_logout(handler, jar).await;
 
     (updated_jar, Redirect::temporary(&url.to_string()))
 }
+
+pub async fn logout_callback(
+    jar: CookieJar,
+    Query(query): Query<LogoutCallbackQuery>,
+    State(settings): State<Settings>,
+) -> impl IntoResponse {
+    let (updated_jar, _, url) = oauth2_logout_callback(settings.server, jar, query);
+
+    (updated_jar, Redirect::temporary(&url.to_string()))
+}
+
+pub async fn backchannel_logout(
+    provider: Option<Path<String>>,
+    State(providers): State<OAuthProviders>,
+    Form(request): Form<BackchannelLogoutRequest>,
+) -> impl IntoResponse {
+    let Some(handler) = providers.get(provider.as_ref().map(|p| p.0.as_str())) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let (status, error) = oauth2_backchannel_logout(handler, request).await;
+    let headers = [(CACHE_CONTROL, "no-store")];
+
+    match error {
+        Some(error_description) => (
+            status,
+            headers,
+            Json(ErrorResponse {
+                error: "invalid_request".to_string(),
+                error_description,
+            }),
+        )
+            .into_response(),
+        None => (status, headers).into_response(),
+    }
+}
+
+pub async fn frontchannel_logout(
+    jar: CookieJar,
+    provider: Option<Path<String>>,
+    Query(query): Query<FrontchannelLogoutQuery>,
+    State(providers): State<OAuthProviders>,
+) -> impl IntoResponse {
+    let Some(handler) = providers.get(provider.as_ref().map(|p| p.0.as_str())) else {
+        return (StatusCode::NOT_FOUND, jar).into_response();
+    };
+    let (updated_jar, status) = oauth2_frontchannel_logout(handler, jar, query).await;
+
+    (status, [(CACHE_CONTROL, "no-store")], updated_jar).into_response()
+}
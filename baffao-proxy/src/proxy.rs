@@ -1,18 +1,24 @@
 use axum::{
     extract::{Request, State},
-    http::{StatusCode, Uri},
+    http::{header::WWW_AUTHENTICATE, StatusCode, Uri},
     response::IntoResponse,
 };
 use axum_extra::extract::CookieJar;
-use baffao::{handlers::proxy, oauth::OAuthHttpHandler};
+use baffao::{
+    error::AuthorizationError,
+    handlers::{proxy, UpstreamToken, IDENTITY_HEADERS},
+    oauth::{OAuthProviders, DPOP_HEADER, DPOP_NONCE_HEADER},
+};
 
-use crate::settings::Settings;
+use crate::settings::{ProxyRoute, Settings};
 use crate::state::HttpClient;
 
+const MACHINE_SECRET_HEADER: &str = "x-machine-secret";
+
 pub async fn handler(
     jar: CookieJar,
     State(client): State<HttpClient>,
-    State(handler): State<OAuthHttpHandler>,
+    State(providers): State<OAuthProviders>,
     State(settings): State<Settings>,
     mut req: Request,
 ) -> impl IntoResponse {
@@ -21,31 +27,226 @@ pub async fn handler(
     }
     let proxy_settings = settings.proxy.as_ref().unwrap();
 
-    let path = req.uri().path();
-    let path_query = req
-        .uri()
-        .path_and_query()
-        .map(|v| v.as_str())
-        .unwrap_or(path);
-    let uri = format!(
-        "http://{}:{}{}",
-        proxy_settings.host, proxy_settings.port, path_query
-    );
-
-    let (updated_jar, headers) = proxy(handler, jar)
-        .await
-        .map_err(|_| StatusCode::BAD_GATEWAY)
-        .unwrap();
+    // The route is matched against the path the upstream receives
+    let Some(path) = normalize_path(req.uri().path()) else {
+        return (jar, StatusCode::BAD_REQUEST.into_response());
+    };
+    let path_query = match req.uri().query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.clone(),
+    };
+    let route = proxy_settings.route(&path);
+    let uri = proxy_settings.upstream_uri(route, &path_query);
+    let upstream_token = match route {
+        Some(ProxyRoute {
+            machine: Some(true),
+            scope,
+            resource,
+            ..
+        }) => UpstreamToken::ClientCredentials(scope.as_deref(), resource.as_deref()),
+        Some(ProxyRoute {
+            path,
+            token_exchange: Some(request),
+            ..
+        }) => UpstreamToken::Exchanged(path, request),
+        Some(ProxyRoute {
+            resource: Some(resource),
+            ..
+        }) => UpstreamToken::Resource(resource),
+        _ => UpstreamToken::Session,
+    };
+
+    let handler = match upstream_token {
+        UpstreamToken::ClientCredentials(..) => {
+            // The client token is only attached for callers holding the route secret
+            if !route.is_some_and(|route| is_machine_caller(route, &req)) {
+                return (jar, StatusCode::UNAUTHORIZED.into_response());
+            }
+            providers.default_provider()
+        }
+        _ => providers.for_session(&jar).await,
+    };
+    // Machine routes have no user authentication to step up nor granted scopes
+    let user_route = route.filter(|route| !route.machine.unwrap_or(false));
+    let authentication = user_route.map(ProxyRoute::authentication);
+    let required_scopes = user_route.and_then(|route| route.required_scopes.as_deref());
+    let (updated_jar, headers) = match proxy(
+        handler.clone(),
+        jar.clone(),
+        req.method(),
+        &uri,
+        upstream_token,
+        authentication.as_ref(),
+        required_scopes,
+    )
+    .await
+    {
+        Ok(response) => response,
+        Err(e) => {
+            return match e.downcast_ref::<AuthorizationError>() {
+                Some(error) => (
+                    jar,
+                    (
+                        error.status_code(),
+                        [(WWW_AUTHENTICATE, error.www_authenticate())],
+                    )
+                        .into_response(),
+                ),
+                None => (jar, StatusCode::BAD_GATEWAY.into_response()),
+            };
+        }
+    };
 
-    *req.uri_mut() = Uri::try_from(uri).unwrap();
+    *req.uri_mut() = Uri::try_from(uri.as_str()).unwrap();
+    // A proof sent by the browser must not reach the upstream along with our token
+    req.headers_mut().remove(DPOP_HEADER);
+    req.headers_mut().remove(MACHINE_SECRET_HEADER);
+    for header in IDENTITY_HEADERS {
+        req.headers_mut().remove(header);
+    }
     req.headers_mut().extend(headers);
 
-    (
-        updated_jar,
-        client
-            .request(req)
-            .await
-            .map_err(|_| StatusCode::BAD_GATEWAY)
-            .into_response(),
-    )
+    let response = match client.request(req).await {
+        Ok(response) => response,
+        Err(_) => return (updated_jar, StatusCode::BAD_GATEWAY.into_response()),
+    };
+    // The upstream may require a nonce in the proofs, it is then returned along with a 401
+    if let Some(nonce) = response
+        .headers()
+        .get(DPOP_NONCE_HEADER)
+        .and_then(|nonce| nonce.to_str().ok())
+    {
+        handler.client().record_dpop_nonce(&uri, nonce);
+    }
+
+    (updated_jar, response.into_response())
+}
+
+fn is_machine_caller(route: &ProxyRoute, req: &Request) -> bool {
+    let (Some(secret), Some(presented)) = (
+        route.machine_secret.as_deref(),
+        req.headers().get(MACHINE_SECRET_HEADER),
+    ) else {
+        return false;
+    };
+
+    constant_time_eq(secret.as_bytes(), presented.as_bytes())
+}
+
+// Compares every byte so that the time taken does not reveal the matching prefix
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/**
+ * Path with the percent-encoded unreserved characters decoded, the dot segments resolved and the
+ * empty segments removed, so that `/public/../admin` or `/%61dmin` cannot bypass the `/admin`
+ * route. None when the path goes above the root or contains a separator the upstream may decode.
+ * URI Normalization: https://datatracker.ietf.org/doc/html/rfc3986#section-6.2.2
+*/
+fn normalize_path(path: &str) -> Option<String> {
+    let mut decoded = String::with_capacity(path.len());
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => {
+                let hex = chars.by_ref().take(2).collect::<String>();
+                if hex.len() != 2 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return None;
+                }
+                let byte = u8::from_str_radix(&hex, 16).ok()?;
+                match byte {
+                    b'/' | b'\\' => return None,
+                    byte if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) => {
+                        decoded.push(byte as char)
+                    }
+                    byte => decoded.push_str(&format!("%{:02X}", byte)),
+                }
+            }
+            '\\' => return None,
+            c => decoded.push(c),
+        }
+    }
+
+    let mut segments = Vec::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            segment => segments.push(segment),
+        }
+    }
+    // A trailing slash or dot segment designates a directory
+    let directory = decoded.ends_with('/') || decoded.ends_with("/.") || decoded.ends_with("/..");
+    let mut normalized = format!("/{}", segments.join("/"));
+    if directory && !segments.is_empty() {
+        normalized.push('/');
+    }
+
+    Some(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::settings::ProxyConfig;
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path("/").as_deref(), Some("/"));
+        assert_eq!(
+            normalize_path("/api/users/").as_deref(),
+            Some("/api/users/")
+        );
+        assert_eq!(
+            normalize_path("/public/../admin").as_deref(),
+            Some("/admin")
+        );
+        assert_eq!(
+            normalize_path("/public/./..//admin/.").as_deref(),
+            Some("/admin/")
+        );
+        assert_eq!(normalize_path("/%61dmin").as_deref(), Some("/admin"));
+        assert_eq!(
+            normalize_path("/public/%2e%2E/admin").as_deref(),
+            Some("/admin")
+        );
+        assert_eq!(
+            normalize_path("/a%20b%c3%a9").as_deref(),
+            Some("/a%20b%C3%A9")
+        );
+        assert!(normalize_path("/..").is_none());
+        assert!(normalize_path("/public/..%2fadmin").is_none());
+        assert!(normalize_path("/public\\..\\admin").is_none());
+        assert!(normalize_path("/admin%2").is_none());
+        assert!(normalize_path("/admin%+1").is_none());
+    }
+
+    #[test]
+    fn test_route_normalized_path() {
+        let config: ProxyConfig = serde_json::from_value(json!({
+            "host": "localhost",
+            "port": 8080,
+            "routes": [
+                { "path": "/public" },
+                { "path": "/admin", "acr_values": ["mfa"] },
+            ],
+        }))
+        .unwrap();
+        let route = |path: &str| {
+            config
+                .route(&normalize_path(path).unwrap())
+                .map(|route| route.path.as_str())
+        };
+
+        assert_eq!(route("/public/../admin/users"), Some("/admin"));
+        assert_eq!(route("/%61dmin"), Some("/admin"));
+        assert_eq!(route("//admin"), Some("/admin"));
+        assert_eq!(route("/public/admin"), Some("/public"));
+        assert_eq!(route("/other"), None);
+    }
 }
@@ -1,17 +1,62 @@
-use axum::{extract::State, response::IntoResponse, Json};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use axum_extra::extract::cookie::CookieJar;
-use baffao::{handlers::get_session_from_cookie, session::Session};
+use baffao::{
+    handlers::{get_session_from_cookie, get_userinfo as get_userinfo_from_provider},
+    oauth::OAuthProviders,
+    session::Session,
+};
+use chrono::{DateTime, Utc};
 use serde::Serialize;
+use serde_json::{Map, Value};
 
 use crate::settings::Settings;
 
 #[derive(Serialize)]
 struct SessionResponse {
-    session: Option<Session>,
+    session: Option<SessionInfo>,
 }
 
-pub async fn get_session(jar: CookieJar, State(settings): State<Settings>) -> impl IntoResponse {
-    let (updated_jar, session) = get_session_from_cookie(jar, settings.server.cookies);
+#[derive(Serialize)]
+struct SessionInfo {
+    id: String,
+    iat: DateTime<Utc>,
+    exp: Option<DateTime<Utc>>,
+    scopes: Option<Vec<String>>,
+    claims: Map<String, Value>,
+}
+
+impl SessionInfo {
+    fn new(session: Session, exposed_claims: &[String]) -> Self {
+        Self {
+            id: session.id().to_string(),
+            iat: session.issued_at(),
+            exp: session.expire(),
+            scopes: session.scopes().cloned(),
+            claims: session.exposed_claims(exposed_claims),
+        }
+    }
+}
+
+pub async fn get_session(
+    jar: CookieJar,
+    State(providers): State<OAuthProviders>,
+    State(settings): State<Settings>,
+) -> impl IntoResponse {
+    let (updated_jar, session) = get_session_from_cookie(providers.default_provider(), jar).await;
+    let session =
+        session.map(|session| SessionInfo::new(session, &settings.server.session.exposed_claims));
 
     (updated_jar, Json(SessionResponse { session }))
 }
+
+pub async fn get_userinfo(
+    jar: CookieJar,
+    State(providers): State<OAuthProviders>,
+) -> impl IntoResponse {
+    let handler = providers.for_session(&jar).await;
+    match get_userinfo_from_provider(handler, jar.clone()).await {
+        Ok((updated_jar, Some(userinfo))) => (updated_jar, Json(userinfo).into_response()),
+        Ok((updated_jar, None)) => (updated_jar, StatusCode::UNAUTHORIZED.into_response()),
+        Err(_) => (jar, StatusCode::BAD_GATEWAY.into_response()),
+    }
+}
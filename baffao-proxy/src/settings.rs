@@ -1,9 +1,9 @@
 use config::{Config, ConfigError, Environment, File};
 use serde::Deserialize;
-use std::env;
+use std::{collections::HashMap, env};
 
 use baffao::{
-    oauth::OAuthConfig,
+    oauth::{AuthenticationRequirement, OAuthConfig, TokenExchangeRequest},
     settings::{JwtConfig, ServerConfig},
 };
 
@@ -11,18 +11,128 @@ use baffao::{
 pub struct ProxyConfig {
     pub host: String,
     pub port: u16,
+    pub tls: Option<bool>,
+    #[serde(default)]
+    pub routes: Vec<ProxyRoute>,
+}
+
+/**
+ * ProxyRoute
+ *
+ * Requests whose path starts with `path` go to this upstream, the host, port and tls default
+ * to the proxy ones. When `resource` is set, the upstream receives an access token restricted
+ * to this resource. With `token_exchange`, it receives a token the session access token is
+ * exchanged for instead. `machine` routes receive a client credentials token with the route
+ * `scope` and `resource`, they do not need a user session but the caller must send the
+ * `machine_secret` in the `x-machine-secret` header, a route without secret is never
+ * authorized. The secret is not forwarded to the upstream. The session must have been
+ * authenticated with one of the `acr_values` and less than `max_age` seconds ago, and must
+ * have been granted the `required_scopes`.
+*/
+#[derive(Deserialize, Clone)]
+pub struct ProxyRoute {
+    pub path: String,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub tls: Option<bool>,
+    pub resource: Option<String>,
+    pub token_exchange: Option<TokenExchangeRequest>,
+    pub machine: Option<bool>,
+    pub machine_secret: Option<String>,
+    pub scope: Option<Vec<String>>,
+    pub acr_values: Option<Vec<String>>,
+    pub max_age: Option<u64>,
+    pub required_scopes: Option<Vec<String>>,
+}
+
+impl ProxyRoute {
+    pub fn authentication(&self) -> AuthenticationRequirement {
+        AuthenticationRequirement {
+            acr_values: self.acr_values.clone(),
+            max_age: self.max_age,
+        }
+    }
+}
+
+impl ProxyConfig {
+    // The most specific route matching the path
+    pub fn route(&self, path: &str) -> Option<&ProxyRoute> {
+        self.routes
+            .iter()
+            .filter(|route| {
+                let prefix = route.path.trim_end_matches('/');
+                path == prefix
+                    || path
+                        .strip_prefix(prefix)
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+            .max_by_key(|route| route.path.len())
+    }
+
+    pub fn upstream_uri(&self, route: Option<&ProxyRoute>, path_query: &str) -> String {
+        let tls = route.and_then(|route| route.tls).or(self.tls);
+        let scheme = if tls.unwrap_or(false) {
+            "https"
+        } else {
+            "http"
+        };
+        let host = route
+            .and_then(|route| route.host.as_deref())
+            .unwrap_or(&self.host);
+        let port = route.and_then(|route| route.port).unwrap_or(self.port);
+
+        format!("{}://{}:{}{}", scheme, host, port, path_query)
+    }
+
+    pub fn resources(&self) -> Vec<String> {
+        let mut resources = self
+            .routes
+            .iter()
+            .filter(|route| !route.machine.unwrap_or(false))
+            .filter_map(|route| route.resource.clone())
+            .collect::<Vec<_>>();
+        resources.sort();
+        resources.dedup();
+        resources
+    }
 }
 
 #[derive(Deserialize, Clone)]
 pub struct Settings {
     pub server: ServerConfig,
     pub oauth: OAuthConfig,
+    #[serde(default)]
+    pub providers: HashMap<String, OAuthConfig>,
     pub jwt: Option<JwtConfig>,
     pub proxy: Option<ProxyConfig>,
     pub debug: bool,
 }
 
 impl Settings {
+    /**
+     * The resources of the proxy routes are requested from every provider.
+     */
+    pub fn with_route_resources(mut self) -> Self {
+        let resources = self
+            .proxy
+            .as_ref()
+            .map(ProxyConfig::resources)
+            .unwrap_or_default();
+        if resources.is_empty() {
+            return self;
+        }
+
+        for config in std::iter::once(&mut self.oauth).chain(self.providers.values_mut()) {
+            let configured = config.resources.get_or_insert_with(Vec::new);
+            for resource in &resources {
+                if !configured.contains(resource) {
+                    configured.push(resource.clone());
+                }
+            }
+        }
+        self
+    }
+
     pub fn new() -> Result<Self, ConfigError> {
         let run_mode = env::var("RUN_MODE").unwrap_or_else(|_| "development".into());
 
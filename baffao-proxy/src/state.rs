@@ -1,21 +1,56 @@
+use anyhow::{Context, Error};
 use axum::{body::Body, extract::FromRef};
-use baffao::oauth::OAuthHttpHandler;
-use hyper_util::client::legacy::connect::HttpConnector;
+use baffao::oauth::{OAuthProviders, TlsClientConfig};
+use hyper_tls::HttpsConnector;
+use hyper_util::{client::legacy::connect::HttpConnector, rt::TokioExecutor};
 
 use crate::settings::Settings;
 
-pub type HttpClient = hyper_util::client::legacy::Client<HttpConnector, Body>;
+pub type HttpClient = hyper_util::client::legacy::Client<HttpsConnector<HttpConnector>, Body>;
+
+/**
+ * The client certificate is presented to the upstreams as well so that certificate-bound
+ * access tokens are accepted.
+*/
+pub fn build_http_client(tls: Option<&TlsClientConfig>) -> Result<HttpClient, Error> {
+    let mut builder = native_tls::TlsConnector::builder();
+    if let Some(tls) = tls {
+        let (certificate, key) = tls.read_identity()?;
+        builder.identity(
+            native_tls::Identity::from_pkcs8(&certificate, &key)
+                .context("Invalid client certificate")?,
+        );
+        if let Some(ca_certificate) = tls.read_ca_certificate()? {
+            builder.add_root_certificate(
+                native_tls::Certificate::from_pem(&ca_certificate)
+                    .context("Invalid CA certificate")?,
+            );
+        }
+    }
+    let tls_connector = builder
+        .build()
+        .context("Failed to build the TLS connector")?;
+
+    let mut http_connector = HttpConnector::new();
+    http_connector.enforce_http(false);
+    let connector = HttpsConnector::from((
+        http_connector,
+        tokio_native_tls::TlsConnector::from(tls_connector),
+    ));
+
+    Ok(hyper_util::client::legacy::Client::builder(TokioExecutor::new()).build(connector))
+}
 
 #[derive(Clone)]
 pub struct AppState {
     pub client: HttpClient,
-    pub oauth_http_handler: OAuthHttpHandler,
+    pub oauth_providers: OAuthProviders,
     pub settings: Settings,
 }
 
-impl FromRef<AppState> for OAuthHttpHandler {
+impl FromRef<AppState> for OAuthProviders {
     fn from_ref(state: &AppState) -> Self {
-        state.oauth_http_handler.clone()
+        state.oauth_providers.clone()
     }
 }
 
@@ -11,6 +11,14 @@ pub fn new_cookie(config: settings::CookieConfig, value: String) -> Cookie<'stat
         .build()
 }
 
+// The removal cookie must match the domain and path of the cookie to be removed
+pub fn remove_cookie(config: settings::CookieConfig) -> Cookie<'static> {
+    Cookie::build(config.name)
+        .domain(config.domain)
+        .path("/")
+        .build()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -37,4 +45,21 @@ mod tests {
         assert_eq!(cookie.http_only(), Some(true));
         assert_eq!(cookie.same_site(), Some(cookie::SameSite::Strict));
     }
+
+    #[test]
+    fn test_remove_cookie() {
+        let config = settings::CookieConfig {
+            name: "test_cookie".to_string(),
+            domain: "example.com".to_string(),
+            secure: true,
+            http_only: true,
+            same_site: cookie::SameSite::Strict,
+        };
+
+        let cookie = remove_cookie(config);
+
+        assert_eq!(cookie.name(), "test_cookie");
+        assert_eq!(cookie.domain(), Some("example.com"));
+        assert_eq!(cookie.path(), Some("/"));
+    }
 }
@@ -1,3 +1,228 @@
+use std::fmt;
+
+use http::StatusCode;
+use oauth2::url::form_urlencoded;
+
+use crate::oauth::AuthenticationRequirement;
+
 pub fn build_error_redirect_url(error_url: &str, message: &str) -> String {
-    format!("{}?&message={}", error_url, message)
+    build_redirect_url(error_url, &[("message", message)])
+}
+
+/**
+ * Append the url encoded parameters to the query of `url`, which may be relative.
+*/
+fn build_redirect_url(url: &str, params: &[(&str, &str)]) -> String {
+    let query = form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(params)
+        .finish();
+    let separator = if url.contains('?') { '&' } else { '?' };
+    format!("{}{}{}", url, separator, query)
+}
+
+/**
+ * AuthorizationResponseError
+ *
+ * Error returned by the provider to the redirect uri instead of an authorization code.
+ * Error Response: https://datatracker.ietf.org/doc/html/rfc6749#section-4.1.2.1
+ * OpenID Connect errors: https://openid.net/specs/openid-connect-core-1_0.html#AuthError
+*/
+#[derive(Debug, PartialEq)]
+pub enum AuthorizationResponseError {
+    AccessDenied,
+    LoginRequired,
+    ConsentRequired,
+    InteractionRequired,
+    Other(String),
+}
+
+impl AuthorizationResponseError {
+    pub fn from_code(code: &str) -> Self {
+        match code {
+            "access_denied" => AuthorizationResponseError::AccessDenied,
+            "login_required" => AuthorizationResponseError::LoginRequired,
+            "consent_required" => AuthorizationResponseError::ConsentRequired,
+            "interaction_required" => AuthorizationResponseError::InteractionRequired,
+            code => AuthorizationResponseError::Other(code.to_string()),
+        }
+    }
+
+    pub fn error_code(&self) -> &str {
+        match self {
+            AuthorizationResponseError::AccessDenied => "access_denied",
+            AuthorizationResponseError::LoginRequired => "login_required",
+            AuthorizationResponseError::ConsentRequired => "consent_required",
+            AuthorizationResponseError::InteractionRequired => "interaction_required",
+            AuthorizationResponseError::Other(code) => code,
+        }
+    }
+
+    // The user has to interact with the provider, a silent login cannot succeed
+    pub fn requires_interaction(&self) -> bool {
+        matches!(
+            self,
+            AuthorizationResponseError::LoginRequired
+                | AuthorizationResponseError::ConsentRequired
+                | AuthorizationResponseError::InteractionRequired
+        )
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            AuthorizationResponseError::AccessDenied => "Access denied",
+            AuthorizationResponseError::LoginRequired => "Login required",
+            AuthorizationResponseError::ConsentRequired => "Consent required",
+            AuthorizationResponseError::InteractionRequired => "Interaction required",
+            AuthorizationResponseError::Other(_) => "Authorization failed",
+        }
+    }
+
+    /**
+     * Error url with the error code and the description of the provider.
+     */
+    pub fn redirect_url(&self, error_url: &str, description: Option<&str>) -> String {
+        let mut params = vec![("error", self.error_code()), ("message", self.message())];
+        if let Some(description) = description {
+            params.push(("error_description", description));
+        }
+
+        build_redirect_url(error_url, &params)
+    }
+}
+
+/**
+ * AuthorizationError
+ *
+ * Reasons to reject a proxied request, they are returned to the client as a Bearer challenge.
+ * Bearer Token Usage: https://datatracker.ietf.org/doc/html/rfc6750#section-3
+ * Step-Up Authentication Challenge: https://datatracker.ietf.org/doc/html/rfc9470#section-3
+*/
+#[derive(Debug)]
+pub enum AuthorizationError {
+    InvalidToken(String),
+    InsufficientUserAuthentication(AuthenticationRequirement),
+    // Scopes missing from the session
+    InsufficientScope(Vec<String>),
+}
+
+impl AuthorizationError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AuthorizationError::InvalidToken(_) => StatusCode::UNAUTHORIZED,
+            AuthorizationError::InsufficientUserAuthentication(_) => StatusCode::UNAUTHORIZED,
+            AuthorizationError::InsufficientScope(_) => StatusCode::FORBIDDEN,
+        }
+    }
+
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            AuthorizationError::InvalidToken(_) => "invalid_token",
+            AuthorizationError::InsufficientUserAuthentication(_) => {
+                "insufficient_user_authentication"
+            }
+            AuthorizationError::InsufficientScope(_) => "insufficient_scope",
+        }
+    }
+
+    pub fn www_authenticate(&self) -> String {
+        let challenge = format!(
+            "Bearer error=\"{}\", error_description=\"{}\"",
+            self.error_code(),
+            self
+        );
+        match self {
+            // The level to restart the login at
+            AuthorizationError::InsufficientUserAuthentication(requirement) => requirement
+                .params()
+                .into_iter()
+                .fold(challenge, |challenge, (name, value)| {
+                    format!("{}, {}=\"{}\"", challenge, name, value)
+                }),
+            // The scopes to request with /oauth/authorize?mode=add
+            AuthorizationError::InsufficientScope(scopes) => {
+                format!("{}, scope=\"{}\"", challenge, scopes.join(" "))
+            }
+            _ => challenge,
+        }
+    }
+}
+
+impl fmt::Display for AuthorizationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthorizationError::InvalidToken(description) => write!(f, "{}", description),
+            AuthorizationError::InsufficientUserAuthentication(_) => {
+                write!(f, "A different authentication level is required")
+            }
+            AuthorizationError::InsufficientScope(_) => {
+                write!(f, "The request requires additional scopes")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AuthorizationError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_error_redirect_url() {
+        assert_eq!(
+            build_error_redirect_url(
+                "/error",
+                "Failed to push: {\"error\":\"invalid_request\"}&x=1"
+            ),
+            "/error?message=Failed+to+push%3A+%7B%22error%22%3A%22invalid_request%22%7D%26x%3D1"
+        );
+    }
+
+    #[test]
+    fn test_authorization_response_error_redirect_url() {
+        let error = AuthorizationResponseError::from_code("access_denied");
+        assert_eq!(error, AuthorizationResponseError::AccessDenied);
+        assert_eq!(
+            error.redirect_url("/error", Some("The user denied the request")),
+            "/error?error=access_denied&message=Access+denied&error_description=The+user+denied+the+request"
+        );
+
+        let error = AuthorizationResponseError::from_code("server_error");
+        assert_eq!(
+            error.redirect_url("https://app.example.com/error", None),
+            "https://app.example.com/error?error=server_error&message=Authorization+failed"
+        );
+
+        let error = AuthorizationResponseError::from_code("login_required");
+        assert!(error.requires_interaction());
+        assert_eq!(
+            error.redirect_url("/orders?page=2", None),
+            "/orders?page=2&error=login_required&message=Login+required"
+        );
+    }
+
+    #[test]
+    fn test_insufficient_user_authentication_challenge() {
+        let error = AuthorizationError::InsufficientUserAuthentication(AuthenticationRequirement {
+            acr_values: Some(vec!["mfa".to_string()]),
+            max_age: Some(300),
+        });
+        assert_eq!(
+            error.www_authenticate(),
+            "Bearer error=\"insufficient_user_authentication\", error_description=\"A different authentication level is required\", acr_values=\"mfa\", max_age=\"300\""
+        );
+    }
+
+    #[test]
+    fn test_insufficient_scope_challenge() {
+        let error = AuthorizationError::InsufficientScope(vec![
+            "billing:read".to_string(),
+            "billing:write".to_string(),
+        ]);
+        assert_eq!(error.status_code(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            error.www_authenticate(),
+            "Bearer error=\"insufficient_scope\", error_description=\"The request requires additional scopes\", scope=\"billing:read billing:write\""
+        );
+    }
 }
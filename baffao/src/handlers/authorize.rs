@@ -2,22 +2,66 @@ use axum_extra::extract::CookieJar;
 use reqwest::StatusCode;
 use serde::Deserialize;
 
-use crate::oauth::OAuthHttpHandler;
+use crate::{
+    error::build_error_redirect_url,
+    oauth::{AuthenticationRequirement, AuthorizationRequest, OAuthHttpHandler},
+    settings::ServerConfig,
+};
 
+/**
+ * AuthorizationQuery
+ *
+ * `acr_values` and `max_age` restart the login at the authentication level a route requires.
+ * `prompt=none` attempts a silent login, the application is returned to the `return_to` path
+ * whether it succeeds or the user has to log in interactively.
+ * With `mode=add`, the `scope` is requested in addition to the scopes granted to the session.
+*/
 #[derive(Deserialize)]
 pub struct AuthorizationQuery {
     pub scope: Option<String>,
+    pub provider: Option<String>,
+    pub acr_values: Option<String>,
+    pub max_age: Option<u64>,
+    pub prompt: Option<String>,
+    pub return_to: Option<String>,
+    pub mode: Option<ScopeMode>,
 }
 
-pub fn oauth2_authorize(
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScopeMode {
+    #[default]
+    Replace,
+    Add,
+}
+
+impl From<AuthorizationQuery> for AuthorizationRequest {
+    fn from(query: AuthorizationQuery) -> Self {
+        let split = |value: String| value.split(' ').map(String::from).collect();
+        AuthorizationRequest::new(query.scope.map(split))
+            .with_authentication(AuthenticationRequirement {
+                acr_values: query.acr_values.map(split),
+                max_age: query.max_age,
+            })
+            .with_prompt(query.prompt)
+            .with_return_to(query.return_to)
+            .with_incremental(query.mode == Some(ScopeMode::Add))
+    }
+}
+
+pub async fn oauth2_authorize(
     handler: OAuthHttpHandler,
+    config: ServerConfig,
     jar: CookieJar,
     query: Option<AuthorizationQuery>,
 ) -> (CookieJar, StatusCode, String) {
-    let scope = query
-        .and_then(|q| q.scope)
-        .map(|scope| scope.split(' ').map(String::from).collect());
-    let (updated_jar, url) = handler.authorize(jar, scope);
-
-    (updated_jar, StatusCode::TEMPORARY_REDIRECT, url.to_string())
+    let authorization_request = query.map(AuthorizationRequest::from).unwrap_or_default();
+    match handler.authorize(jar.clone(), &authorization_request).await {
+        Ok((updated_jar, url)) => (updated_jar, StatusCode::TEMPORARY_REDIRECT, url),
+        Err(e) => (
+            jar,
+            StatusCode::TEMPORARY_REDIRECT,
+            build_error_redirect_url(&config.error_url, &e.to_string()),
+        ),
+    }
 }
@@ -0,0 +1,19 @@
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::oauth::OAuthHttpHandler;
+
+#[derive(Deserialize)]
+pub struct BackchannelLogoutRequest {
+    pub logout_token: String,
+}
+
+pub async fn oauth2_backchannel_logout(
+    handler: OAuthHttpHandler,
+    request: BackchannelLogoutRequest,
+) -> (StatusCode, Option<String>) {
+    match handler.backchannel_logout(&request.logout_token).await {
+        Ok(()) => (StatusCode::OK, None),
+        Err(e) => (StatusCode::BAD_REQUEST, Some(e.to_string())),
+    }
+}
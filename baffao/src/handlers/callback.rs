@@ -1,23 +1,53 @@
-use axum_extra::extract::cookie::{Cookie, CookieJar};
+use axum_extra::extract::cookie::CookieJar;
+use http::Method;
 use reqwest::StatusCode;
 use serde::Deserialize;
 
 use crate::{
-    error::build_error_redirect_url,
-    oauth::OAuthHttpHandler,
+    cookies::remove_cookie,
+    error::{build_error_redirect_url, AuthorizationResponseError},
+    oauth::{AuthorizationResponseClaims, AuthorizationState, OAuthHttpHandler},
     settings::{CookiesConfig, ServerConfig},
 };
 
+/**
+ * AuthorizationCallbackQuery
+ *
+ * Either the authorization code or the error returned by the provider, both with the state.
+ * With the JWT response modes, they are all in the signed `response`.
+*/
 #[derive(Deserialize)]
 pub struct AuthorizationCallbackQuery {
-    pub code: String,
-    pub state: String,
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub iss: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+    pub response: Option<String>,
 }
 
+impl From<AuthorizationResponseClaims> for AuthorizationCallbackQuery {
+    fn from(claims: AuthorizationResponseClaims) -> Self {
+        Self {
+            code: claims.code,
+            state: claims.state,
+            iss: Some(claims.iss),
+            error: claims.error,
+            error_description: claims.error_description,
+            response: None,
+        }
+    }
+}
+
+/**
+ * Complete the login with the authorization response, received with `method`: the form post
+ * response modes are only accepted with POST and the others with GET.
+*/
 pub async fn oauth2_callback(
     handler: OAuthHttpHandler,
     config: ServerConfig,
     jar: CookieJar,
+    method: &Method,
     query: AuthorizationCallbackQuery,
 ) -> (CookieJar, StatusCode, String) {
     let ServerConfig {
@@ -26,28 +56,105 @@ pub async fn oauth2_callback(
             CookiesConfig {
                 oauth_csrf: oauth_csrf_cookie,
                 oauth_pkce: oauth_pkce_cookie,
+                oauth_nonce: oauth_nonce_cookie,
                 ..
             },
         ..
     } = config;
 
-    let pkce_code = jar
+    let response_mode = handler.client().config().response_mode.unwrap_or_default();
+    if response_mode.is_form_post() != (method == Method::POST) {
+        return (
+            jar,
+            StatusCode::TEMPORARY_REDIRECT,
+            build_error_redirect_url(&error_url, "Response mode mismatch"),
+        );
+    }
+    let query = match (response_mode.is_jwt(), query.response.as_deref()) {
+        (false, _) => query,
+        (true, None) => {
+            return (
+                jar,
+                StatusCode::TEMPORARY_REDIRECT,
+                build_error_redirect_url(&error_url, "Authorization response not found"),
+            );
+        }
+        (true, Some(response)) => match handler
+            .client()
+            .validate_authorization_response(response)
+            .await
+        {
+            Ok(claims) => AuthorizationCallbackQuery::from(claims),
+            Err(e) => {
+                return (
+                    jar,
+                    StatusCode::TEMPORARY_REDIRECT,
+                    build_error_redirect_url(&error_url, &e.to_string()),
+                );
+            }
+        },
+    };
+
+    let state = jar
         .get(oauth_csrf_cookie.name.as_str())
-        .map(|cookie| cookie.value().to_string());
-    if pkce_code.is_none() {
+        .map(|cookie| AuthorizationState::decode(cookie.value()));
+    let state = match state {
+        None => {
+            return (
+                jar,
+                StatusCode::TEMPORARY_REDIRECT,
+                build_error_redirect_url(&error_url, "CSRF token not found"),
+            );
+        }
+        Some(Ok(state)) if query.state.as_deref() == Some(state.csrf_token.as_str()) => state,
+        Some(_) => {
+            return (
+                jar,
+                StatusCode::TEMPORARY_REDIRECT,
+                build_error_redirect_url(&error_url, "CSRF token mismatch"),
+            );
+        }
+    };
+
+    // The login must be completed by the provider it was started with
+    if state.provider() != handler.provider() {
         return (
             jar,
             StatusCode::TEMPORARY_REDIRECT,
-            build_error_redirect_url(&error_url, "CSRF token not found"),
+            build_error_redirect_url(&error_url, "Provider mismatch"),
         );
-    } else if pkce_code.unwrap() != query.state {
+    }
+
+    // Error responses carry the issuer as well
+    let iss_required = handler
+        .client()
+        .metadata()
+        .await
+        .authorization_response_iss_parameter_supported
+        .unwrap_or(false);
+    if let Err(e) = state.validate_issuer(query.iss.as_deref(), iss_required) {
         return (
             jar,
             StatusCode::TEMPORARY_REDIRECT,
-            build_error_redirect_url(&error_url, "CSRF token mismatch"),
+            build_error_redirect_url(&error_url, &e.to_string()),
         );
     }
 
+    if let Some(error) = query.error {
+        let updated_jar = jar
+            .remove(remove_cookie(oauth_csrf_cookie))
+            .remove(remove_cookie(oauth_pkce_cookie))
+            .remove(remove_cookie(oauth_nonce_cookie));
+        let error = AuthorizationResponseError::from_code(&error);
+        // A failed silent login returns to the application which can then log in interactively
+        let url = if state.is_silent() && error.requires_interaction() {
+            error.redirect_url(state.return_to(), None)
+        } else {
+            error.redirect_url(&error_url, query.error_description.as_deref())
+        };
+        return (updated_jar, StatusCode::TEMPORARY_REDIRECT, url);
+    }
+
     let pkce_verifier = jar
         .get(oauth_pkce_cookie.name.as_str())
         .map(|cookie| cookie.value().to_string());
@@ -59,20 +166,41 @@ pub async fn oauth2_callback(
         );
     }
 
-    if query.code.is_empty() {
+    let nonce = jar
+        .get(oauth_nonce_cookie.name.as_str())
+        .map(|cookie| cookie.value().to_string());
+    if nonce.is_none() {
         return (
             jar,
             StatusCode::TEMPORARY_REDIRECT,
-            build_error_redirect_url(&error_url, "Authorization code not found"),
+            build_error_redirect_url(&error_url, "Nonce not found"),
         );
     }
 
+    let code = match query.code {
+        Some(code) if !code.is_empty() => code,
+        _ => {
+            return (
+                jar,
+                StatusCode::TEMPORARY_REDIRECT,
+                build_error_redirect_url(&error_url, "Authorization code not found"),
+            );
+        }
+    };
+
     let mut updated_jar = jar
-        .remove(Cookie::from(oauth_csrf_cookie.name))
-        .remove(Cookie::from(oauth_pkce_cookie.name));
+        .remove(remove_cookie(oauth_csrf_cookie))
+        .remove(remove_cookie(oauth_pkce_cookie))
+        .remove(remove_cookie(oauth_nonce_cookie));
 
     updated_jar = match handler
-        .exchange_code(updated_jar.to_owned(), query.code, pkce_verifier.unwrap())
+        .exchange_code(
+            updated_jar.to_owned(),
+            code,
+            pkce_verifier.unwrap(),
+            nonce.unwrap(),
+            state.scope().cloned(),
+        )
         .await
     {
         Ok(response) => response,
@@ -85,5 +213,9 @@ pub async fn oauth2_callback(
         }
     };
 
-    (updated_jar, StatusCode::TEMPORARY_REDIRECT, "/".to_string())
+    (
+        updated_jar,
+        StatusCode::TEMPORARY_REDIRECT,
+        state.return_to().to_string(),
+    )
 }
@@ -0,0 +1,25 @@
+use axum_extra::extract::CookieJar;
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::oauth::OAuthHttpHandler;
+
+#[derive(Deserialize)]
+pub struct FrontchannelLogoutQuery {
+    pub iss: Option<String>,
+    pub sid: Option<String>,
+}
+
+pub async fn oauth2_frontchannel_logout(
+    handler: OAuthHttpHandler,
+    jar: CookieJar,
+    query: FrontchannelLogoutQuery,
+) -> (CookieJar, StatusCode) {
+    match handler
+        .frontchannel_logout(jar.clone(), query.iss, query.sid)
+        .await
+    {
+        Ok(updated_jar) => (updated_jar, StatusCode::OK),
+        Err(_) => (jar, StatusCode::BAD_REQUEST),
+    }
+}
@@ -0,0 +1,12 @@
+use anyhow::Error;
+use axum_extra::extract::CookieJar;
+use serde_json::{Map, Value};
+
+use crate::oauth::OAuthHttpHandler;
+
+pub async fn get_userinfo(
+    handler: OAuthHttpHandler,
+    jar: CookieJar,
+) -> Result<(CookieJar, Option<Map<String, Value>>), Error> {
+    handler.get_userinfo(jar).await
+}
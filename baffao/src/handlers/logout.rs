@@ -0,0 +1,60 @@
+use axum_extra::extract::cookie::CookieJar;
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::{
+    cookies::remove_cookie,
+    error::build_error_redirect_url,
+    oauth::OAuthHttpHandler,
+    settings::{CookiesConfig, ServerConfig},
+};
+
+#[derive(Deserialize)]
+pub struct LogoutCallbackQuery {
+    pub state: Option<String>,
+}
+
+pub async fn oauth2_logout(
+    handler: OAuthHttpHandler,
+    jar: CookieJar,
+) -> (CookieJar, StatusCode, String) {
+    let (updated_jar, url) = handler.logout(jar).await;
+
+    (
+        updated_jar,
+        StatusCode::TEMPORARY_REDIRECT,
+        url.unwrap_or_else(|| "/".to_string()),
+    )
+}
+
+pub fn oauth2_logout_callback(
+    config: ServerConfig,
+    jar: CookieJar,
+    query: LogoutCallbackQuery,
+) -> (CookieJar, StatusCode, String) {
+    let ServerConfig {
+        error_url,
+        cookies: CookiesConfig {
+            oauth_csrf: oauth_csrf_cookie,
+            ..
+        },
+        ..
+    } = config;
+
+    let csrf_token = jar
+        .get(oauth_csrf_cookie.name.as_str())
+        .map(|cookie| cookie.value().to_string());
+    if csrf_token.is_none() || csrf_token != query.state {
+        return (
+            jar,
+            StatusCode::TEMPORARY_REDIRECT,
+            build_error_redirect_url(&error_url, "Logout state mismatch"),
+        );
+    }
+
+    (
+        jar.remove(remove_cookie(oauth_csrf_cookie)),
+        StatusCode::TEMPORARY_REDIRECT,
+        "/".to_string(),
+    )
+}
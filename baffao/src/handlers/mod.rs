@@ -1,9 +1,19 @@
-pub use authorize::{oauth2_authorize, AuthorizationQuery};
+pub use authorize::{oauth2_authorize, AuthorizationQuery, ScopeMode};
+pub use backchannel_logout::{oauth2_backchannel_logout, BackchannelLogoutRequest};
 pub use callback::{oauth2_callback, AuthorizationCallbackQuery};
+pub use frontchannel_logout::{oauth2_frontchannel_logout, FrontchannelLogoutQuery};
 pub use get_session::get_session_from_cookie;
-pub use proxy::proxy;
+pub use get_userinfo::get_userinfo;
+pub use logout::{oauth2_logout, oauth2_logout_callback, LogoutCallbackQuery};
+pub use proxy::{
+    proxy, UpstreamToken, EXPIRES_HEADER, IDENTITY_HEADERS, SCOPE_HEADER, SUBJECT_HEADER,
+};
 
 mod authorize;
+mod backchannel_logout;
 mod callback;
+mod frontchannel_logout;
 mod get_session;
+mod get_userinfo;
+mod logout;
 mod proxy;
@@ -1,23 +1,161 @@
-use anyhow::{Error, Ok};
+use anyhow::{Context, Error, Ok};
 use axum_extra::extract::CookieJar;
-use http::HeaderMap;
+use http::{header::AUTHORIZATION, HeaderMap, Method};
+use reqwest::Url;
 
-use crate::oauth::OAuthHttpHandler;
+use crate::{
+    error::AuthorizationError,
+    oauth::{
+        AuthenticationRequirement, OAuthHttpHandler, TokenExchangeRequest, DPOP_HEADER,
+        DPOP_TOKEN_TYPE,
+    },
+};
 
+/**
+ * Headers carrying the introspected token to the upstream, they must be stripped from
+ * the incoming request.
+*/
+pub const SUBJECT_HEADER: &str = "x-auth-subject";
+pub const SCOPE_HEADER: &str = "x-auth-scope";
+pub const EXPIRES_HEADER: &str = "x-auth-expires";
+pub const IDENTITY_HEADERS: [&str; 3] = [SUBJECT_HEADER, SCOPE_HEADER, EXPIRES_HEADER];
+
+/**
+ * UpstreamToken
+ *
+ * Access token the upstream receives: the session one, the one restricted to a resource,
+ * the one the session token is exchanged for, cached under the upstream name, or a token of
+ * the client itself for the machine routes which do not need a session.
+*/
+pub enum UpstreamToken<'a> {
+    Session,
+    Resource(&'a str),
+    Exchanged(&'a str, &'a TokenExchangeRequest),
+    ClientCredentials(Option<&'a [String]>, Option<&'a str>),
+}
+
+/**
+ * Build the headers authorizing the upstream request `method` `uri`. DPoP-bound access tokens
+ * are sent with a proof for this request. The session must meet the `authentication`
+ * requirement of the route, otherwise a step-up challenge is returned, and must have been
+ * granted the `required_scopes`, otherwise the missing scopes are returned.
+*/
 pub async fn proxy(
     handler: OAuthHttpHandler,
     jar: CookieJar,
+    method: &Method,
+    uri: &str,
+    upstream_token: UpstreamToken<'_>,
+    authentication: Option<&AuthenticationRequirement>,
+    required_scopes: Option<&[String]>,
 ) -> Result<(CookieJar, HeaderMap), Error> {
-    let (updated_jar, access_token) = handler.get_or_refresh_token(jar).await?;
+    if let Some(requirement) = authentication.filter(|requirement| !requirement.is_empty()) {
+        let (_, session) = handler.get_session(jar.clone()).await;
+        if !session
+            .is_some_and(|session| requirement.is_satisfied_by(session.acr(), session.auth_time()))
+        {
+            return Err(
+                AuthorizationError::InsufficientUserAuthentication(requirement.clone()).into(),
+            );
+        }
+    }
+    if let Some(required_scopes) = required_scopes.filter(|scopes| !scopes.is_empty()) {
+        let (_, session) = handler.get_session(jar.clone()).await;
+        let granted_scopes = session
+            .as_ref()
+            .and_then(|session| session.scopes())
+            .cloned()
+            .unwrap_or_default();
+        let missing_scopes = required_scopes
+            .iter()
+            .filter(|scope| !granted_scopes.contains(scope))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !missing_scopes.is_empty() {
+            return Err(AuthorizationError::InsufficientScope(missing_scopes).into());
+        }
+    }
+
+    let (updated_jar, access_token) = match upstream_token {
+        UpstreamToken::Session => handler.get_or_refresh_token(jar).await?,
+        UpstreamToken::Resource(resource) => handler.get_resource_token(jar, resource).await?,
+        UpstreamToken::Exchanged(upstream, request) => {
+            handler.get_exchanged_token(jar, upstream, request).await?
+        }
+        // Machine routes do not depend on the session
+        UpstreamToken::ClientCredentials(scope, resource) => {
+            let access_token = handler
+                .client()
+                .client_credentials_token(scope, resource)
+                .await?;
+            let mut headers = HeaderMap::new();
+            headers.insert(AUTHORIZATION, format!("Bearer {}", access_token).parse()?);
+            return Ok((jar, headers));
+        }
+    };
     if access_token.is_none() {
         return Ok((updated_jar, HeaderMap::new()));
     }
+    let access_token = access_token.unwrap();
 
     let mut headers = HeaderMap::new();
-    headers.insert(
-        "Authorization",
-        format!("Bearer {}", access_token.unwrap()).parse()?,
-    );
+    let (updated_jar, session) = handler.get_session(updated_jar).await;
+    // Resource and exchanged tokens are only bound to the DPoP key when issued as DPoP tokens
+    let dpop_bound = match (&session, &upstream_token) {
+        (None, _) | (_, UpstreamToken::ClientCredentials(..)) => false,
+        (Some(_), UpstreamToken::Session) => true,
+        (Some(session), UpstreamToken::Resource(resource)) => session
+            .resource_token(resource)
+            .is_some_and(|token| token.dpop),
+        (Some(session), UpstreamToken::Exchanged(upstream, _)) => session
+            .exchanged_token(upstream)
+            .is_some_and(|token| token.dpop),
+    };
+    let dpop_key = match session.as_ref().filter(|_| dpop_bound) {
+        Some(session) => handler.dpop_key(session)?,
+        None => None,
+    };
+    match dpop_key {
+        Some(dpop_key) => {
+            let url = Url::parse(uri).context("Invalid upstream uri")?;
+            let proof =
+                handler
+                    .client()
+                    .dpop_proof(&dpop_key, method.as_str(), &url, &access_token)?;
+            headers.insert(
+                AUTHORIZATION,
+                format!("{} {}", DPOP_TOKEN_TYPE, access_token).parse()?,
+            );
+            headers.insert(DPOP_HEADER, proof.parse()?);
+        }
+        None => {
+            headers.insert(AUTHORIZATION, format!("Bearer {}", access_token).parse()?);
+        }
+    }
+
+    // Only the session token describes the session
+    let (updated_jar, introspected_token) = match upstream_token {
+        UpstreamToken::Session => handler.introspect(updated_jar, &access_token).await?,
+        _ => (updated_jar, handler.introspect_token(&access_token).await?),
+    };
+    if let Some(introspected_token) = introspected_token {
+        // The session token is refreshed once inactive, the other tokens are not used again
+        if !introspected_token.active {
+            return Err(AuthorizationError::InvalidToken(
+                "The access token is inactive".to_string(),
+            )
+            .into());
+        }
+        if let Some(sub) = introspected_token.sub {
+            headers.insert(SUBJECT_HEADER, sub.parse()?);
+        }
+        if let Some(scope) = introspected_token.scope {
+            headers.insert(SCOPE_HEADER, scope.join(" ").parse()?);
+        }
+        if let Some(exp) = introspected_token.exp {
+            headers.insert(EXPIRES_HEADER, exp.timestamp().into());
+        }
+    }
 
     Ok((updated_jar, headers))
 }
@@ -0,0 +1,109 @@
+use super::AuthenticationRequirement;
+
+const PROMPT_NONE: &str = "none";
+
+/**
+ * AuthorizationRequest
+ *
+ * What the application asks for when starting a login, the configured default scopes are
+ * requested when `scope` is not set. With `prompt=none` the login is silent: the provider
+ * must not display any page and answers with an error when the user has to interact.
+ * The application is returned to the `return_to` path once logged in.
+ * An `incremental` request asks for its scopes in addition to the ones already granted to the
+ * session instead of replacing them.
+*/
+#[derive(Clone, Debug, Default)]
+pub struct AuthorizationRequest {
+    pub scope: Option<Vec<String>>,
+    pub incremental: bool,
+    pub authentication: AuthenticationRequirement,
+    pub prompt: Option<String>,
+    pub return_to: Option<String>,
+}
+
+impl AuthorizationRequest {
+    pub fn new(scope: Option<Vec<String>>) -> Self {
+        Self {
+            scope,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_authentication(mut self, authentication: AuthenticationRequirement) -> Self {
+        self.authentication = authentication;
+        self
+    }
+
+    pub fn with_incremental(mut self, incremental: bool) -> Self {
+        self.incremental = incremental;
+        self
+    }
+
+    pub fn with_prompt(mut self, prompt: Option<String>) -> Self {
+        self.prompt = prompt;
+        self
+    }
+
+    pub fn with_return_to(mut self, return_to: Option<String>) -> Self {
+        self.return_to = return_to;
+        self
+    }
+
+    pub fn is_silent(&self) -> bool {
+        self.prompt.as_deref() == Some(PROMPT_NONE)
+    }
+
+    // Scopes to request, the granted ones come first when the request is incremental
+    pub fn scopes(
+        &self,
+        default_scopes: &[String],
+        granted_scopes: Option<&Vec<String>>,
+    ) -> Vec<String> {
+        let requested = self.scope.as_deref().unwrap_or(default_scopes);
+        let granted = match granted_scopes {
+            Some(granted_scopes) if self.incremental => granted_scopes.as_slice(),
+            _ => &[],
+        };
+
+        let mut scopes: Vec<String> = vec![];
+        for scope in granted.iter().chain(requested) {
+            if !scopes.contains(scope) {
+                scopes.push(scope.clone());
+            }
+        }
+        scopes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scopes(scopes: &[&str]) -> Vec<String> {
+        scopes.iter().map(|scope| scope.to_string()).collect()
+    }
+
+    #[test]
+    fn test_scopes() {
+        let default_scopes = scopes(&["openid", "profile"]);
+        let granted_scopes = scopes(&["openid", "orders:read"]);
+
+        let request = AuthorizationRequest::new(Some(scopes(&["openid", "billing:read"])));
+        assert_eq!(
+            request.scopes(&default_scopes, Some(&granted_scopes)),
+            scopes(&["openid", "billing:read"])
+        );
+
+        let request = request.with_incremental(true);
+        assert_eq!(
+            request.scopes(&default_scopes, Some(&granted_scopes)),
+            scopes(&["openid", "orders:read", "billing:read"])
+        );
+
+        let request = AuthorizationRequest::default().with_incremental(true);
+        assert_eq!(
+            request.scopes(&default_scopes, None),
+            scopes(&["openid", "profile"])
+        );
+    }
+}
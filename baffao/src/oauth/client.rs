@@ -1,76 +1,540 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
 use anyhow::{Context, Error};
+use chrono::Utc;
+use jsonwebtoken::{decode_header, Algorithm, DecodingKey, Header};
 use oauth2::{
-    basic::BasicClient, reqwest::async_http_client, AuthType, AuthUrl, AuthorizationCode, ClientId,
-    ClientSecret, CsrfToken, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, RefreshToken, Scope,
-    TokenUrl,
+    basic::{
+        BasicErrorResponse, BasicRevocationErrorResponse, BasicTokenIntrospectionResponse,
+        BasicTokenType,
+    },
+    url::form_urlencoded,
+    AccessToken as OAuthAccessToken, AuthType, AuthUrl, AuthorizationCode,
+    ClientCredentialsTokenRequest, ClientId, ClientSecret, CodeTokenRequest, CsrfToken,
+    HttpRequest, HttpResponse, IntrospectionRequest, IntrospectionUrl, PkceCodeChallenge,
+    PkceCodeVerifier, RedirectUrl, RefreshToken, RefreshTokenRequest, RequestTokenError,
+    RevocationRequest, RevocationUrl, Scope, StandardRevocableToken, TokenResponse, TokenUrl,
+};
+use reqwest::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    Url,
 };
-use reqwest::Url;
+use serde_json::{Map, Value};
+
+use super::{
+    client_auth::{ClientAssertionClaims, ClientAuthMethod, CLIENT_ASSERTION_TYPE},
+    client_credentials::ClientCredentialsCache,
+    dpop::{DpopKey, DpopNonces, DPOP_HEADER, DPOP_NONCE_HEADER, DPOP_TOKEN_TYPE},
+    http_client::{is_use_dpop_nonce_error, send_dpop_request, send_request, HttpClientError},
+    id_token::{validate_id_token, IdTokenValidation},
+    introspection::{IntrospectedToken, IntrospectionCache},
+    jwks::JwksCache,
+    logout_token::{validate_logout_token, LogoutTokenReplayCache},
+    metadata::is_hmac,
+    par::{
+        authorization_url_with_request_uri, push_authorization_request, PushedAuthorizationRequests,
+    },
+    request_object::{authorization_url_with_request, request_object_claims, REQUEST_OBJECT_TYPE},
+    response_mode::{validate_authorization_response, AuthorizationResponseClaims},
+    signing_key::SigningKey,
+    tls::build_http_client,
+    token_exchange::{parse_token_exchange_response, TokenExchangeRequest, TokenExchangeResponse},
+    userinfo::validate_userinfo,
+    AccessToken, AuthorizationRequest, IdTokenClaims, LogoutTokenClaims, OAuthConfig,
+    ProviderMetadata,
+};
+
+const DEFAULT_METADATA_REFRESH_INTERVAL: u64 = 3600;
+const DEFAULT_CLOCK_SKEW: u64 = 60;
+const DEFAULT_INTROSPECTION_CACHE_TTL: u64 = 30;
 
-use super::{AccessToken, OAuthConfig};
+type Client = oauth2::Client<
+    BasicErrorResponse,
+    AccessToken,
+    BasicTokenType,
+    BasicTokenIntrospectionResponse,
+    StandardRevocableToken,
+    BasicRevocationErrorResponse,
+>;
+
+/**
+ * Requests of the oauth2 client the client assertion is added to.
+*/
+trait ExtraParams {
+    fn with_extra_params(self, params: Vec<(String, String)>) -> Self;
+}
+
+macro_rules! impl_extra_params {
+    ($($request:ty),* $(,)?) => {
+        $(
+            impl ExtraParams for $request {
+                fn with_extra_params(self, params: Vec<(String, String)>) -> Self {
+                    params.into_iter().fold(self, |request, (name, value)| {
+                        request.add_extra_param(name, value)
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_extra_params!(
+    CodeTokenRequest<'_, BasicErrorResponse, AccessToken, BasicTokenType>,
+    RefreshTokenRequest<'_, BasicErrorResponse, AccessToken, BasicTokenType>,
+    ClientCredentialsTokenRequest<'_, BasicErrorResponse, AccessToken, BasicTokenType>,
+    RevocationRequest<'_, StandardRevocableToken, BasicRevocationErrorResponse>,
+    IntrospectionRequest<'_, BasicErrorResponse, BasicTokenIntrospectionResponse, BasicTokenType>,
+);
+
+struct Provider {
+    metadata: ProviderMetadata,
+    client: Client,
+    fetched_at: Instant,
+}
 
 pub struct OAuthClient {
     config: OAuthConfig,
-    client: BasicClient,
+    http_client: reqwest::Client,
+    provider: Arc<RwLock<Provider>>,
+    jwks: JwksCache,
+    introspection_cache: IntrospectionCache,
+    signing_key: Option<SigningKey>,
+    dpop_nonces: DpopNonces,
+    client_credentials_cache: ClientCredentialsCache,
+    logout_token_replay_cache: LogoutTokenReplayCache,
 }
 
 impl Clone for OAuthClient {
     fn clone(&self) -> Self {
         OAuthClient {
             config: self.config.clone(),
-            client: self.client.clone(),
+            http_client: self.http_client.clone(),
+            provider: self.provider.clone(),
+            jwks: self.jwks.clone(),
+            introspection_cache: self.introspection_cache.clone(),
+            signing_key: self.signing_key.clone(),
+            dpop_nonces: self.dpop_nonces.clone(),
+            client_credentials_cache: self.client_credentials_cache.clone(),
+            logout_token_replay_cache: self.logout_token_replay_cache.clone(),
         }
     }
 }
 
 impl OAuthClient {
-    pub fn new(config: OAuthConfig) -> Result<Self, Error> {
+    pub async fn new(config: OAuthConfig) -> Result<Self, Error> {
+        let auth_method = config.client_auth_method.unwrap_or_default();
+        if auth_method.requires_client_secret() && config.client_secret.is_none() {
+            return Err(Error::msg(format!(
+                "Missing client secret for {:?} client authentication",
+                auth_method
+            )));
+        }
+        if auth_method == ClientAuthMethod::PrivateKeyJwt && config.signing_key.is_none() {
+            return Err(Error::msg(
+                "Missing signing key for private_key_jwt client authentication",
+            ));
+        }
+
+        if auth_method.requires_client_certificate() && config.tls.is_none() {
+            return Err(Error::msg(format!(
+                "Missing client certificate for {:?} client authentication",
+                auth_method
+            )));
+        }
+
+        let http_client = build_http_client(config.tls.as_ref())?;
+        let metadata = Self::load_metadata(&config, &http_client).await?;
+        if config.introspection.unwrap_or(false) && metadata.introspection_endpoint.is_none() {
+            return Err(Error::msg(
+                "Missing introspection endpoint to introspect the access tokens",
+            ));
+        }
+        let client = Self::build_client(&config, &metadata)?;
+        let signing_key = config
+            .signing_key
+            .as_ref()
+            .map(SigningKey::load)
+            .transpose()?;
+
+        Ok(Self {
+            config,
+            http_client,
+            provider: Arc::new(RwLock::new(Provider {
+                metadata,
+                client,
+                fetched_at: Instant::now(),
+            })),
+            jwks: JwksCache::default(),
+            introspection_cache: IntrospectionCache::default(),
+            signing_key,
+            dpop_nonces: DpopNonces::default(),
+            client_credentials_cache: ClientCredentialsCache::default(),
+            logout_token_replay_cache: LogoutTokenReplayCache::default(),
+        })
+    }
+
+    async fn load_metadata(
+        config: &OAuthConfig,
+        http_client: &reqwest::Client,
+    ) -> Result<ProviderMetadata, Error> {
+        let metadata = match &config.issuer {
+            Some(issuer) => ProviderMetadata::discover(http_client, issuer)
+                .await
+                .context("Failed to discover provider metadata")?,
+            None => ProviderMetadata::default(),
+        };
+
+        Ok(metadata.with_overrides(config))
+    }
+
+    fn build_client(config: &OAuthConfig, metadata: &ProviderMetadata) -> Result<Client, Error> {
         let redirect_uri = RedirectUrl::new(config.authorization_redirect_uri.clone())
             .context("Failed to parse redirect uri")?;
-        let auth_url = AuthUrl::new(config.authorization_endpoint.clone())
-            .context("Failed to parse authorization url")?;
-        let token_endpoint =
-            TokenUrl::new(config.token_endpoint.clone()).context("Failed to parse token url")?;
+        let auth_url = AuthUrl::new(
+            metadata
+                .authorization_endpoint
+                .clone()
+                .context("Missing authorization endpoint")?,
+        )
+        .context("Failed to parse authorization url")?;
+        let token_endpoint = TokenUrl::new(
+            metadata
+                .token_endpoint
+                .clone()
+                .context("Missing token endpoint")?,
+        )
+        .context("Failed to parse token url")?;
 
-        let client = BasicClient::new(
+        // With the JWT based methods, the client assertion is added to each request instead
+        let (client_secret, auth_type) = match config.client_auth_method.unwrap_or_default() {
+            ClientAuthMethod::ClientSecretBasic => {
+                (config.client_secret.clone(), AuthType::BasicAuth)
+            }
+            ClientAuthMethod::ClientSecretPost => {
+                (config.client_secret.clone(), AuthType::RequestBody)
+            }
+            _ => (None, AuthType::RequestBody),
+        };
+
+        let client = Client::new(
             ClientId::new(config.client_id.clone()),
-            Some(ClientSecret::new(config.client_secret.clone())),
+            client_secret.map(ClientSecret::new),
             auth_url,
             Some(token_endpoint),
         )
-        .set_auth_type(AuthType::RequestBody)
+        .set_auth_type(auth_type)
         .set_redirect_uri(redirect_uri);
 
-        Ok(Self { config, client })
+        let client = match &metadata.revocation_endpoint {
+            Some(revocation_endpoint) => client.set_revocation_uri(
+                RevocationUrl::new(revocation_endpoint.clone())
+                    .context("Failed to parse revocation url")?,
+            ),
+            None => client,
+        };
+        let client = match &metadata.introspection_endpoint {
+            Some(introspection_endpoint) => client.set_introspection_uri(
+                IntrospectionUrl::new(introspection_endpoint.clone())
+                    .context("Failed to parse introspection url")?,
+            ),
+            None => client,
+        };
+
+        Ok(client)
     }
 
-    pub fn build_authorization_endpoint(
+    fn is_stale(&self) -> bool {
+        let refresh_interval = Duration::from_secs(
+            self.config
+                .metadata_refresh_interval
+                .unwrap_or(DEFAULT_METADATA_REFRESH_INTERVAL),
+        );
+        self.config.issuer.is_some()
+            && self.provider.read().unwrap().fetched_at.elapsed() >= refresh_interval
+    }
+
+    /**
+     * Re-fetch the provider metadata. On failure the previous metadata is kept.
+     */
+    pub async fn refresh_metadata(&self) -> Result<(), Error> {
+        // Mark the metadata as fresh first so that concurrent requests do not fetch it again
+        self.provider.write().unwrap().fetched_at = Instant::now();
+
+        let metadata = Self::load_metadata(&self.config, &self.http_client).await?;
+        let client = Self::build_client(&self.config, &metadata)?;
+
+        let mut provider = self.provider.write().unwrap();
+        provider.metadata = metadata;
+        provider.client = client;
+        Ok(())
+    }
+
+    async fn refresh_metadata_if_stale(&self) {
+        if self.is_stale() {
+            // Keep serving the cached metadata when the provider is unreachable
+            let _ = self.refresh_metadata().await;
+        }
+    }
+
+    pub async fn metadata(&self) -> ProviderMetadata {
+        self.refresh_metadata_if_stale().await;
+        self.provider.read().unwrap().metadata.clone()
+    }
+
+    async fn client(&self) -> Client {
+        self.refresh_metadata_if_stale().await;
+        self.provider.read().unwrap().client.clone()
+    }
+
+    pub async fn build_authorization_endpoint(
         &self,
-        scope: Option<Vec<String>>,
-    ) -> (Url, CsrfToken, PkceCodeVerifier) {
-        let scopes =
-            scope.unwrap_or_else(|| self.config.default_scopes.clone().unwrap_or_default());
+        authorization_request: &AuthorizationRequest,
+    ) -> Result<(Url, CsrfToken, PkceCodeVerifier, String), Error> {
+        let scopes = authorization_request
+            .scope
+            .clone()
+            .unwrap_or_else(|| self.config.default_scopes.clone().unwrap_or_default());
         let (pkce_code_challenge, pkce_code_verifier) = PkceCodeChallenge::new_random_sha256();
+        let nonce = CsrfToken::new_random().secret().to_string();
 
-        let (url, csrf_token) = self
-            .client
+        let resources = self.config.resources.clone().unwrap_or_default();
+
+        let client = self.client().await;
+        let request = client
             .authorize_url(CsrfToken::new_random)
             .add_scopes(scopes.iter().map(|s| Scope::new(s.clone())))
             .set_pkce_challenge(pkce_code_challenge)
+            .add_extra_param("nonce", &nonce);
+        let request = match self.config.response_mode.unwrap_or_default().param() {
+            Some(response_mode) => request.add_extra_param("response_mode", response_mode),
+            None => request,
+        };
+        let request = match &authorization_request.prompt {
+            Some(prompt) => request.add_extra_param("prompt", prompt),
+            None => request,
+        };
+        let request = authorization_request
+            .authentication
+            .params()
+            .into_iter()
+            .fold(request, |request, (name, value)| {
+                request.add_extra_param(name, value)
+            });
+        let (url, csrf_token) = resources
+            .iter()
+            .fold(request, |request, resource| {
+                request.add_extra_param("resource", resource)
+            })
             .url();
 
-        (url, csrf_token, pkce_code_verifier)
+        let url = self.sign_authorization_request(url).await?;
+        let url = self.push_authorization_request(url).await?;
+
+        Ok((url, csrf_token, pkce_code_verifier, nonce))
+    }
+
+    /**
+     * Replace the authorization request parameters with a signed request object when
+     * `request_object` is enabled.
+     */
+    async fn sign_authorization_request(&self, url: Url) -> Result<Url, Error> {
+        if !self.config.request_object.unwrap_or(false) {
+            return Ok(url);
+        }
+
+        let signing_key = self
+            .signing_key
+            .as_ref()
+            .context("Missing signing key to sign the request object")?;
+        let audience = self
+            .metadata()
+            .await
+            .issuer
+            .context("Missing issuer to sign the request object")?;
+        let params = url.query_pairs().into_owned().collect::<Vec<_>>();
+        let claims = request_object_claims(
+            &params,
+            &self.config.client_id,
+            &audience,
+            CsrfToken::new_random().secret(),
+            Utc::now(),
+        );
+        let request = signing_key.sign(REQUEST_OBJECT_TYPE, &claims)?;
+
+        Ok(authorization_url_with_request(
+            &url,
+            &self.config.client_id,
+            &request,
+        ))
+    }
+
+    /**
+     * Push the authorization request parameters to the provider when PAR is enabled and
+     * return the url with the resulting request uri.
+     */
+    async fn push_authorization_request(&self, url: Url) -> Result<Url, Error> {
+        let metadata = self.metadata().await;
+        let mode = if metadata.require_pushed_authorization_requests == Some(true) {
+            PushedAuthorizationRequests::Required
+        } else {
+            self.config
+                .pushed_authorization_requests
+                .unwrap_or_default()
+        };
+        let endpoint = match (mode, metadata.pushed_authorization_request_endpoint) {
+            (PushedAuthorizationRequests::Disabled, _) => return Ok(url),
+            (PushedAuthorizationRequests::Enabled, None) => return Ok(url),
+            (PushedAuthorizationRequests::Required, None) => return Err(Error::msg(
+                "Pushed authorization requests are required but the provider has no PAR endpoint",
+            )),
+            (_, Some(endpoint)) => endpoint,
+        };
+
+        let params = url
+            .query_pairs()
+            .into_owned()
+            .filter(|(name, _)| name != "client_id")
+            .collect::<Vec<_>>();
+        let response = match self
+            .authenticate(self.http_client.post(&endpoint), params)
+            .await
+        {
+            Ok(request) => push_authorization_request(request).await,
+            Err(e) => Err(e),
+        };
+        match response {
+            Ok(response) => Ok(authorization_url_with_request_uri(
+                &url,
+                &self.config.client_id,
+                &response.request_uri,
+            )),
+            Err(e) if mode == PushedAuthorizationRequests::Required => Err(e),
+            Err(_) => Ok(url),
+        }
+    }
+
+    /**
+     * Client assertion to add to the requests when a JWT based client authentication is used.
+     * The token endpoint is used as audience for all the endpoints.
+     */
+    async fn client_assertion_params(&self) -> Result<Vec<(String, String)>, Error> {
+        let signing_key = match self.config.client_auth_method.unwrap_or_default() {
+            ClientAuthMethod::ClientSecretJwt => SigningKey::from_secret(
+                self.config
+                    .client_secret
+                    .as_deref()
+                    .context("Missing client secret to sign the client assertion")?,
+            ),
+            ClientAuthMethod::PrivateKeyJwt => self
+                .signing_key
+                .clone()
+                .context("Missing signing key to sign the client assertion")?,
+            _ => return Ok(vec![]),
+        };
+        let audience = self
+            .metadata()
+            .await
+            .token_endpoint
+            .context("Missing token endpoint")?;
+        let claims = ClientAssertionClaims::new(
+            &self.config.client_id,
+            &audience,
+            CsrfToken::new_random().secret(),
+            Utc::now(),
+        );
+
+        Ok(vec![
+            (
+                "client_assertion_type".to_string(),
+                CLIENT_ASSERTION_TYPE.to_string(),
+            ),
+            (
+                "client_assertion".to_string(),
+                signing_key.sign("JWT", &claims)?,
+            ),
+        ])
+    }
+
+    // Client authentication for the requests sent through the oauth2 client
+    async fn authenticate_request<R: ExtraParams>(&self, request: R) -> Result<R, Error> {
+        Ok(request.with_extra_params(self.client_assertion_params().await?))
+    }
+
+    // Client authentication for the requests which are not sent through the oauth2 client
+    async fn authenticate(
+        &self,
+        request: reqwest::RequestBuilder,
+        mut params: Vec<(String, String)>,
+    ) -> Result<reqwest::RequestBuilder, Error> {
+        let client_id = self.config.client_id.clone();
+        let client_secret = self.config.client_secret.clone().unwrap_or_default();
+        let request = match self.config.client_auth_method.unwrap_or_default() {
+            ClientAuthMethod::ClientSecretBasic => request.basic_auth(
+                form_urlencoded::byte_serialize(client_id.as_bytes()).collect::<String>(),
+                Some(form_urlencoded::byte_serialize(client_secret.as_bytes()).collect::<String>()),
+            ),
+            ClientAuthMethod::ClientSecretPost => {
+                params.push(("client_id".to_string(), client_id));
+                params.push(("client_secret".to_string(), client_secret));
+                request
+            }
+            // The client assertion is empty with the mutual TLS methods
+            _ => {
+                params.push(("client_id".to_string(), client_id));
+                params.extend(self.client_assertion_params().await?);
+                request
+            }
+        };
+
+        Ok(request.form(&params))
+    }
+
+    /**
+     * Build the RP-Initiated Logout url, the state is only sent along with the post logout redirect uri.
+     * RP-Initiated Logout: https://openid.net/specs/openid-connect-rpinitiated-1_0.html
+     */
+    pub async fn build_end_session_endpoint(
+        &self,
+        id_token_hint: Option<&str>,
+    ) -> Option<(Url, Option<CsrfToken>)> {
+        let end_session_endpoint = self.metadata().await.end_session_endpoint?;
+        let mut url = Url::parse(&end_session_endpoint).ok()?;
+        let mut state = None;
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("client_id", &self.config.client_id);
+            if let Some(id_token_hint) = id_token_hint {
+                query.append_pair("id_token_hint", id_token_hint);
+            }
+            if let Some(post_logout_redirect_uri) = &self.config.post_logout_redirect_uri {
+                let csrf_token = CsrfToken::new_random();
+                query
+                    .append_pair("post_logout_redirect_uri", post_logout_redirect_uri)
+                    .append_pair("state", csrf_token.secret());
+                state = Some(csrf_token);
+            }
+        }
+
+        Some((url, state))
     }
 
     pub async fn exchange_code(
         &self,
         code: String,
         pkce_verifier: String,
+        dpop_key: Option<&DpopKey>,
     ) -> Result<AccessToken, Error> {
-        let response = self
-            .client
+        let client = self.client().await;
+        let request = client
             .exchange_code(AuthorizationCode::new(code))
-            .set_pkce_verifier(PkceCodeVerifier::new(pkce_verifier))
-            .request_async(async_http_client)
+            .set_pkce_verifier(PkceCodeVerifier::new(pkce_verifier));
+        let response = self
+            .authenticate_request(request)
+            .await?
+            .request_async(|request| self.send_request(request, dpop_key))
             .await;
         if let Err(e) = response {
             if e.to_string().contains("invalid_grant") {
@@ -87,11 +551,28 @@ impl OAuthClient {
         Ok(response.unwrap())
     }
 
-    pub async fn refresh_token(&self, refresh_token: String) -> Result<AccessToken, Error> {
+    /**
+     * Refresh the access token, when `resource` is set the new access token is restricted
+     * to this resource.
+     * Resource Indicators: https://datatracker.ietf.org/doc/html/rfc8707
+     */
+    pub async fn refresh_token(
+        &self,
+        refresh_token: String,
+        dpop_key: Option<&DpopKey>,
+        resource: Option<&str>,
+    ) -> Result<AccessToken, Error> {
+        let client = self.client().await;
+        let refresh_token = RefreshToken::new(refresh_token);
+        let request = client.exchange_refresh_token(&refresh_token);
+        let request = match resource {
+            Some(resource) => request.add_extra_param("resource", resource),
+            None => request,
+        };
         let response = self
-            .client
-            .exchange_refresh_token(&RefreshToken::new(refresh_token))
-            .request_async(async_http_client)
+            .authenticate_request(request)
+            .await?
+            .request_async(|request| self.send_request(request, dpop_key))
             .await;
         if let Err(e) = response {
             if e.to_string().contains("invalid_grant") {
@@ -105,4 +586,440 @@ impl OAuthClient {
 
         Ok(response.unwrap())
     }
+
+    async fn send_request(
+        &self,
+        request: HttpRequest,
+        dpop_key: Option<&DpopKey>,
+    ) -> Result<HttpResponse, HttpClientError> {
+        match dpop_key {
+            Some(dpop_key) => {
+                send_dpop_request(&self.http_client, request, dpop_key, &self.dpop_nonces).await
+            }
+            None => send_request(&self.http_client, request).await,
+        }
+    }
+
+    /**
+     * Build the DPoP proof of a request sent with an access token bound to `dpop_key`.
+     * DPoP: https://datatracker.ietf.org/doc/html/rfc9449
+     */
+    pub fn dpop_proof(
+        &self,
+        dpop_key: &DpopKey,
+        method: &str,
+        url: &Url,
+        access_token: &str,
+    ) -> Result<String, Error> {
+        let nonce = self.dpop_nonces.get(url);
+        dpop_key.proof(method, url, Some(access_token), nonce.as_deref())
+    }
+
+    /**
+     * Keep the nonce a resource server returned, including with a 401, for the next proofs sent
+     * to it.
+     */
+    pub fn record_dpop_nonce(&self, url: &str, nonce: &str) {
+        if let Result::Ok(url) = Url::parse(url) {
+            self.dpop_nonces.insert(&url, nonce.to_string());
+        }
+    }
+
+    pub fn is_dpop_enabled(&self) -> bool {
+        self.config.dpop.unwrap_or(false)
+    }
+
+    // Discovery advertising an introspection endpoint does not enable introspection
+    pub fn is_introspection_enabled(&self) -> bool {
+        self.config.introspection.unwrap_or(false)
+    }
+
+    // Tokens are only bound to the key when the provider supports DPoP
+    pub fn is_dpop_bound(token_result: &AccessToken) -> bool {
+        token_result
+            .token_type()
+            .as_ref()
+            .eq_ignore_ascii_case(DPOP_TOKEN_TYPE)
+    }
+
+    /**
+     * Get an access token for the client itself with the client credentials grant. Tokens are
+     * cached and renewed shortly before they expire.
+     */
+    pub async fn client_credentials_token(
+        &self,
+        scope: Option<&[String]>,
+        resource: Option<&str>,
+    ) -> Result<String, Error> {
+        let key = ClientCredentialsCache::key(scope, resource);
+        if let Some(access_token) = self.client_credentials_cache.get(&key) {
+            return Ok(access_token);
+        }
+
+        let client = self.client().await;
+        let request = client
+            .exchange_client_credentials()
+            .add_scopes(scope.unwrap_or_default().iter().cloned().map(Scope::new));
+        let request = match resource {
+            Some(resource) => request.add_extra_param("resource", resource),
+            None => request,
+        };
+        let token_result = self
+            .authenticate_request(request)
+            .await?
+            .request_async(|request| self.send_request(request, None))
+            .await
+            .map_err(|e| match e {
+                RequestTokenError::ServerResponse(response) => Error::msg(format!(
+                    "Failed to obtain a client credentials token: {}",
+                    response
+                )),
+                e => Error::new(e).context("Failed to obtain a client credentials token"),
+            })?;
+
+        let access_token = token_result.access_token().secret().to_string();
+        self.client_credentials_cache
+            .insert(key, access_token.clone(), token_result.expires_in());
+
+        Ok(access_token)
+    }
+
+    /**
+     * Exchange the access token of the user for a token with the requested audience, resource
+     * and scope. A subject token bound to `dpop_key` is sent with a proof of the key, which is
+     * sent again once with the nonce the provider requires.
+     * Token Exchange: https://datatracker.ietf.org/doc/html/rfc8693
+     */
+    pub async fn exchange_token(
+        &self,
+        subject_token: &str,
+        request: &TokenExchangeRequest,
+        dpop_key: Option<&DpopKey>,
+    ) -> Result<TokenExchangeResponse, Error> {
+        let token_endpoint = self
+            .metadata()
+            .await
+            .token_endpoint
+            .context("Missing token endpoint")?;
+        let url = Url::parse(&token_endpoint).context("Invalid token endpoint")?;
+
+        let mut retried = false;
+        loop {
+            let mut http_request = self
+                .authenticate(
+                    self.http_client.post(url.clone()),
+                    request.params(subject_token),
+                )
+                .await?;
+            if let Some(dpop_key) = dpop_key {
+                let nonce = self.dpop_nonces.get(&url);
+                let proof = dpop_key.proof("POST", &url, None, nonce.as_deref())?;
+                http_request = http_request.header(DPOP_HEADER, proof);
+            }
+
+            let response = http_request.send().await?;
+            if let Some(nonce) = response
+                .headers()
+                .get(DPOP_NONCE_HEADER)
+                .and_then(|nonce| nonce.to_str().ok())
+            {
+                self.dpop_nonces.insert(&url, nonce.to_string());
+            }
+            let status = response.status();
+            let body = response.bytes().await?;
+            let use_dpop_nonce = dpop_key.is_some()
+                && status == reqwest::StatusCode::BAD_REQUEST
+                && is_use_dpop_nonce_error(&body);
+            if use_dpop_nonce && !retried {
+                retried = true;
+                continue;
+            }
+
+            return parse_token_exchange_response(status, &body);
+        }
+    }
+
+    /**
+     * Revoke a token at the provider revocation endpoint.
+     * Token Revocation: https://datatracker.ietf.org/doc/html/rfc7009
+     */
+    pub async fn revoke_token(&self, token: StandardRevocableToken) -> Result<(), Error> {
+        let client = self.client().await;
+        let request = client
+            .revoke_token(token)
+            .context("Missing revocation endpoint")?;
+        self.authenticate_request(request)
+            .await?
+            .request_async(|request| self.send_request(request, None))
+            .await
+            .map_err(|e| match e {
+                RequestTokenError::ServerResponse(response) => {
+                    Error::msg(format!("Failed to revoke token: {}", response.error()))
+                }
+                e => Error::new(e).context("Failed to revoke token"),
+            })?;
+
+        Ok(())
+    }
+
+    pub async fn revoke_refresh_token(&self, refresh_token: String) -> Result<(), Error> {
+        self.revoke_token(StandardRevocableToken::RefreshToken(RefreshToken::new(
+            refresh_token,
+        )))
+        .await
+    }
+
+    pub async fn revoke_access_token(&self, access_token: String) -> Result<(), Error> {
+        let response = self
+            .revoke_token(StandardRevocableToken::AccessToken(OAuthAccessToken::new(
+                access_token,
+            )))
+            .await;
+        // Providers are not required to support the revocation of access tokens
+        if let Err(e) = &response {
+            if e.to_string().contains("unsupported_token_type") {
+                return Ok(());
+            }
+        }
+
+        response
+    }
+
+    /**
+     * Introspect a token at the provider introspection endpoint, results are cached for
+     * `introspection_cache_ttl` seconds.
+     * Token Introspection: https://datatracker.ietf.org/doc/html/rfc7662
+     */
+    pub async fn introspect(&self, token: &str) -> Result<IntrospectedToken, Error> {
+        if let Some(introspected_token) = self.introspection_cache.get(token) {
+            return Ok(introspected_token);
+        }
+
+        let client = self.client().await;
+        let access_token = OAuthAccessToken::new(token.to_string());
+        let request = client
+            .introspect(&access_token)
+            .context("Missing introspection endpoint")?;
+        let response = self
+            .authenticate_request(request)
+            .await?
+            .request_async(|request| self.send_request(request, None))
+            .await
+            .map_err(|e| match e {
+                RequestTokenError::ServerResponse(response) => {
+                    Error::msg(format!("Failed to introspect token: {}", response))
+                }
+                e => Error::new(e).context("Failed to introspect token"),
+            })?;
+
+        let introspected_token = IntrospectedToken::from(response);
+        self.introspection_cache.insert(
+            token,
+            introspected_token.clone(),
+            Duration::from_secs(
+                self.config
+                    .introspection_cache_ttl
+                    .unwrap_or(DEFAULT_INTROSPECTION_CACHE_TTL),
+            ),
+        );
+
+        Ok(introspected_token)
+    }
+
+    pub fn config(&self) -> &OAuthConfig {
+        &self.config
+    }
+
+    async fn decoding_key(
+        &self,
+        header: &Header,
+        metadata: &ProviderMetadata,
+        algorithms: &[Algorithm],
+    ) -> Result<DecodingKey, Error> {
+        // The header is not trusted, its algorithm must be one the provider signs with
+        if !algorithms.contains(&header.alg) {
+            return Err(Error::msg(format!(
+                "Signature algorithm {:?} is not allowed",
+                header.alg
+            )));
+        }
+
+        match header.alg {
+            // Symmetric signatures use the client secret as key
+            alg if is_hmac(alg) => Ok(DecodingKey::from_secret(
+                self.config
+                    .client_secret
+                    .as_deref()
+                    .context("Missing client secret to validate the signature")?
+                    .as_bytes(),
+            )),
+            _ => {
+                let jwks_uri = metadata
+                    .jwks_uri
+                    .as_deref()
+                    .context("Missing JWKS uri to validate the signature")?;
+                let jwk = self
+                    .jwks
+                    .find(
+                        &self.http_client,
+                        jwks_uri,
+                        header.kid.as_deref(),
+                        header.alg,
+                    )
+                    .await?;
+                DecodingKey::from_jwk(&jwk).context("Invalid signing key")
+            }
+        }
+    }
+
+    pub async fn validate_id_token(
+        &self,
+        id_token: &str,
+        nonce: Option<&str>,
+    ) -> Result<IdTokenClaims, Error> {
+        let header = decode_header(id_token).context("Invalid ID token")?;
+        let metadata = self.metadata().await;
+        let algorithms = metadata.signing_algorithms(self.config.signing_algorithms.as_deref());
+        let key = self.decoding_key(&header, &metadata, &algorithms).await?;
+        let issuer = metadata
+            .issuer
+            .context("Missing issuer to validate the ID token")?;
+
+        validate_id_token(
+            id_token,
+            &key,
+            header.alg,
+            &IdTokenValidation {
+                issuer: &issuer,
+                client_id: &self.config.client_id,
+                nonce,
+                clock_skew: self.config.clock_skew.unwrap_or(DEFAULT_CLOCK_SKEW),
+            },
+        )
+    }
+
+    pub async fn validate_logout_token(
+        &self,
+        logout_token: &str,
+    ) -> Result<LogoutTokenClaims, Error> {
+        let header = decode_header(logout_token).context("Invalid logout token")?;
+        let metadata = self.metadata().await;
+        let algorithms = metadata.signing_algorithms(self.config.signing_algorithms.as_deref());
+        let key = self.decoding_key(&header, &metadata, &algorithms).await?;
+        let issuer = metadata
+            .issuer
+            .context("Missing issuer to validate the logout token")?;
+
+        let clock_skew = self.config.clock_skew.unwrap_or(DEFAULT_CLOCK_SKEW);
+        let claims = validate_logout_token(
+            logout_token,
+            &key,
+            header.alg,
+            &issuer,
+            &self.config.client_id,
+            clock_skew,
+        )?;
+        self.logout_token_replay_cache.check(&claims, clock_skew)?;
+
+        Ok(claims)
+    }
+
+    pub async fn validate_authorization_response(
+        &self,
+        response: &str,
+    ) -> Result<AuthorizationResponseClaims, Error> {
+        let header = decode_header(response).context("Invalid authorization response")?;
+        let metadata = self.metadata().await;
+        let algorithms =
+            metadata.authorization_signing_algorithms(self.config.signing_algorithms.as_deref());
+        let key = self.decoding_key(&header, &metadata, &algorithms).await?;
+        let issuer = metadata
+            .issuer
+            .context("Missing issuer to validate the authorization response")?;
+
+        validate_authorization_response(
+            response,
+            &key,
+            header.alg,
+            &issuer,
+            &self.config.client_id,
+            self.config.clock_skew.unwrap_or(DEFAULT_CLOCK_SKEW),
+        )
+    }
+
+    /**
+     * Fetch the claims of the user from the UserInfo endpoint, the response can either be
+     * plain JSON or a signed JWT.
+     * UserInfo Endpoint: https://openid.net/specs/openid-connect-core-1_0.html#UserInfo
+     */
+    pub async fn fetch_userinfo(
+        &self,
+        access_token: &str,
+        dpop_key: Option<&DpopKey>,
+    ) -> Result<Map<String, Value>, Error> {
+        let metadata = self.metadata().await;
+        let userinfo_endpoint = metadata
+            .userinfo_endpoint
+            .as_deref()
+            .context("Missing userinfo endpoint")?;
+
+        let request = match dpop_key {
+            Some(dpop_key) => {
+                let url = Url::parse(userinfo_endpoint).context("Invalid userinfo endpoint")?;
+                self.http_client
+                    .get(userinfo_endpoint)
+                    .header(
+                        AUTHORIZATION,
+                        format!("{} {}", DPOP_TOKEN_TYPE, access_token),
+                    )
+                    .header(
+                        DPOP_HEADER,
+                        self.dpop_proof(dpop_key, "GET", &url, access_token)?,
+                    )
+            }
+            None => self
+                .http_client
+                .get(userinfo_endpoint)
+                .bearer_auth(access_token),
+        };
+        let response = request.send().await?;
+        if let Some(nonce) = response
+            .headers()
+            .get(DPOP_NONCE_HEADER)
+            .and_then(|nonce| nonce.to_str().ok())
+        {
+            self.dpop_nonces.insert(response.url(), nonce.to_string());
+        }
+        let response = response
+            .error_for_status()
+            .context("Failed to fetch userinfo")?;
+        let is_jwt = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .is_some_and(|content_type| content_type.starts_with("application/jwt"));
+        if !is_jwt {
+            return response
+                .json::<Map<String, Value>>()
+                .await
+                .context("Invalid userinfo response");
+        }
+
+        let userinfo = response.text().await?;
+        let header = decode_header(&userinfo).context("Invalid userinfo response")?;
+        let algorithms = metadata.signing_algorithms(self.config.signing_algorithms.as_deref());
+        let key = self.decoding_key(&header, &metadata, &algorithms).await?;
+        let issuer = metadata
+            .issuer
+            .context("Missing issuer to validate the userinfo response")?;
+
+        validate_userinfo(
+            &userinfo,
+            &key,
+            header.alg,
+            &issuer,
+            &self.config.client_id,
+            self.config.clock_skew.unwrap_or(DEFAULT_CLOCK_SKEW),
+        )
+    }
 }
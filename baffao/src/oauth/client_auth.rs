@@ -0,0 +1,94 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+pub const CLIENT_ASSERTION_TYPE: &str = "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+const CLIENT_ASSERTION_LIFETIME: i64 = 60;
+
+/**
+ * ClientAuthMethod
+ *
+ * How the client authenticates to the token, revocation, introspection and PAR endpoints.
+ * `client_secret_jwt` signs the client assertion with the client secret and `private_key_jwt`
+ * with the configured signing key. `tls_client_auth` and `self_signed_tls_client_auth` rely on
+ * the client certificate.
+ * JWT Client Authentication: https://datatracker.ietf.org/doc/html/rfc7523
+ * Mutual-TLS Client Authentication: https://datatracker.ietf.org/doc/html/rfc8705
+*/
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientAuthMethod {
+    ClientSecretBasic,
+    #[default]
+    ClientSecretPost,
+    ClientSecretJwt,
+    PrivateKeyJwt,
+    TlsClientAuth,
+    SelfSignedTlsClientAuth,
+}
+
+impl ClientAuthMethod {
+    pub fn requires_client_secret(&self) -> bool {
+        matches!(
+            self,
+            ClientAuthMethod::ClientSecretBasic
+                | ClientAuthMethod::ClientSecretPost
+                | ClientAuthMethod::ClientSecretJwt
+        )
+    }
+
+    pub fn requires_client_certificate(&self) -> bool {
+        matches!(
+            self,
+            ClientAuthMethod::TlsClientAuth | ClientAuthMethod::SelfSignedTlsClientAuth
+        )
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ClientAssertionClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub jti: String,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+impl ClientAssertionClaims {
+    pub fn new(client_id: &str, audience: &str, jti: &str, now: DateTime<Utc>) -> Self {
+        Self {
+            iss: client_id.to_string(),
+            sub: client_id.to_string(),
+            aud: audience.to_string(),
+            jti: jti.to_string(),
+            iat: now.timestamp(),
+            exp: now.timestamp() + CLIENT_ASSERTION_LIFETIME,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_auth_method() {
+        let method: ClientAuthMethod =
+            serde_json::from_value(serde_json::json!("private_key_jwt")).unwrap();
+
+        assert_eq!(method, ClientAuthMethod::PrivateKeyJwt);
+        assert!(!method.requires_client_secret());
+        assert!(ClientAuthMethod::default().requires_client_secret());
+    }
+
+    #[test]
+    fn test_client_assertion_claims() {
+        let now = Utc::now();
+        let claims = ClientAssertionClaims::new("client", "https://example.com/token", "jti", now);
+
+        assert_eq!(claims.iss, "client");
+        assert_eq!(claims.sub, "client");
+        assert_eq!(claims.aud, "https://example.com/token");
+        assert_eq!(claims.exp, now.timestamp() + CLIENT_ASSERTION_LIFETIME);
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+// Tokens are renewed this long before they expire so that they are still valid upstream
+const EXPIRATION_MARGIN: Duration = Duration::from_secs(30);
+
+use super::DEFAULT_TOKEN_TTL;
+
+// Access token and the instant it must be renewed at
+type Entry = (String, Instant);
+
+/**
+ * ClientCredentialsCache
+ *
+ * Tokens obtained with the client credentials grant, keyed by the requested scope and resource.
+*/
+#[derive(Clone, Default)]
+pub struct ClientCredentialsCache {
+    entries: Arc<RwLock<HashMap<String, Entry>>>,
+}
+
+impl ClientCredentialsCache {
+    pub fn key(scope: Option<&[String]>, resource: Option<&str>) -> String {
+        format!(
+            "{}|{}",
+            scope.map(|scope| scope.join(" ")).unwrap_or_default(),
+            resource.unwrap_or_default()
+        )
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        let entries = self.entries.read().unwrap();
+        entries
+            .get(key)
+            .filter(|(_, expires_at)| *expires_at > Instant::now())
+            .map(|(access_token, _)| access_token.clone())
+    }
+
+    pub fn insert(&self, key: String, access_token: String, expires_in: Option<Duration>) {
+        let expires_in = expires_in.unwrap_or(DEFAULT_TOKEN_TTL);
+        let expires_at = Instant::now() + expires_in.saturating_sub(EXPIRATION_MARGIN);
+        self.entries
+            .write()
+            .unwrap()
+            .insert(key, (access_token, expires_at));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_credentials_cache() {
+        let cache = ClientCredentialsCache::default();
+        let key = ClientCredentialsCache::key(Some(&["jobs".to_string()]), None);
+        cache.insert(
+            key.clone(),
+            "token".to_string(),
+            Some(Duration::from_secs(300)),
+        );
+
+        assert_eq!(cache.get(&key).as_deref(), Some("token"));
+        assert!(cache
+            .get(&ClientCredentialsCache::key(None, None))
+            .is_none());
+    }
+
+    #[test]
+    fn test_client_credentials_cache_renews_before_expiration() {
+        let cache = ClientCredentialsCache::default();
+        let key = ClientCredentialsCache::key(None, None);
+        cache.insert(key.clone(), "token".to_string(), Some(EXPIRATION_MARGIN));
+
+        assert!(cache.get(&key).is_none());
+    }
+}
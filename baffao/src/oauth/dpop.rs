@@ -0,0 +1,194 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use anyhow::{Context, Error};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::Utc;
+use jsonwebtoken::{
+    encode,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+        EllipticCurveKeyType, Jwk,
+    },
+    Algorithm, EncodingKey, Header,
+};
+use oauth2::CsrfToken;
+use reqwest::Url;
+use ring::{
+    digest::{digest, SHA256},
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+};
+use serde::{Deserialize, Serialize};
+
+pub const DPOP_HEADER: &str = "dpop";
+pub const DPOP_NONCE_HEADER: &str = "dpop-nonce";
+pub const DPOP_TOKEN_TYPE: &str = "DPoP";
+const DPOP_PROOF_TYPE: &str = "dpop+jwt";
+
+/**
+ * DpopKey
+ *
+ * P-256 key pair the access tokens of a session are bound to, kept as a PKCS#8 document.
+ * Demonstrating Proof of Possession: https://datatracker.ietf.org/doc/html/rfc9449
+*/
+#[derive(Clone)]
+pub struct DpopKey {
+    pkcs8: Vec<u8>,
+    jwk: Jwk,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DpopProofClaims {
+    pub jti: String,
+    pub htm: String,
+    pub htu: String,
+    pub iat: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ath: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+}
+
+impl DpopKey {
+    pub fn generate() -> Result<Self, Error> {
+        let pkcs8 =
+            EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &SystemRandom::new())
+                .map_err(|_| Error::msg("Failed to generate the DPoP key"))?;
+        Self::from_pkcs8(pkcs8.as_ref().to_vec())
+    }
+
+    fn from_pkcs8(pkcs8: Vec<u8>) -> Result<Self, Error> {
+        let key_pair = EcdsaKeyPair::from_pkcs8(
+            &ECDSA_P256_SHA256_FIXED_SIGNING,
+            &pkcs8,
+            &SystemRandom::new(),
+        )
+        .map_err(|_| Error::msg("Invalid DPoP key"))?;
+        // Uncompressed point: 0x04 followed by the x and y coordinates
+        let public_key = key_pair.public_key().as_ref();
+        let jwk = Jwk {
+            common: CommonParameters::default(),
+            algorithm: AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                key_type: EllipticCurveKeyType::EC,
+                curve: EllipticCurve::P256,
+                x: URL_SAFE_NO_PAD.encode(&public_key[1..33]),
+                y: URL_SAFE_NO_PAD.encode(&public_key[33..65]),
+            }),
+        };
+
+        Ok(Self { pkcs8, jwk })
+    }
+
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(&self.pkcs8)
+    }
+
+    pub fn decode(encoded: &str) -> Result<Self, Error> {
+        let pkcs8 = URL_SAFE_NO_PAD
+            .decode(encoded)
+            .context("Invalid DPoP key")?;
+        Self::from_pkcs8(pkcs8)
+    }
+
+    /**
+     * Build a proof for a request, `access_token` is set when the request carries a token
+     * bound to this key.
+     */
+    pub fn proof(
+        &self,
+        htm: &str,
+        htu: &Url,
+        access_token: Option<&str>,
+        nonce: Option<&str>,
+    ) -> Result<String, Error> {
+        let mut header = Header::new(Algorithm::ES256);
+        header.typ = Some(DPOP_PROOF_TYPE.to_string());
+        header.jwk = Some(self.jwk.clone());
+
+        let claims = DpopProofClaims {
+            jti: CsrfToken::new_random().secret().to_string(),
+            htm: htm.to_uppercase(),
+            htu: target_uri(htu),
+            iat: Utc::now().timestamp(),
+            ath: access_token.map(access_token_hash),
+            nonce: nonce.map(String::from),
+        };
+
+        encode(&header, &claims, &EncodingKey::from_ec_der(&self.pkcs8))
+            .context("Failed to sign the DPoP proof")
+    }
+}
+
+/**
+ * DpopNonces
+ *
+ * Last nonce provided by each server, sent back in the following proofs.
+*/
+#[derive(Clone, Default)]
+pub struct DpopNonces {
+    nonces: Arc<RwLock<HashMap<String, String>>>,
+}
+
+impl DpopNonces {
+    pub fn get(&self, url: &Url) -> Option<String> {
+        self.nonces
+            .read()
+            .unwrap()
+            .get(&url.origin().ascii_serialization())
+            .cloned()
+    }
+
+    pub fn insert(&self, url: &Url, nonce: String) {
+        self.nonces
+            .write()
+            .unwrap()
+            .insert(url.origin().ascii_serialization(), nonce);
+    }
+}
+
+// The htu claim is the request uri without query and fragment
+fn target_uri(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_query(None);
+    url.set_fragment(None);
+    url.to_string()
+}
+
+fn access_token_hash(access_token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(digest(&SHA256, access_token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{decode, decode_header, DecodingKey, Validation};
+
+    #[test]
+    fn test_dpop_proof() {
+        let key = DpopKey::decode(&DpopKey::generate().unwrap().encode()).unwrap();
+        let url = Url::parse("https://api.example.com/resource?query=value#fragment").unwrap();
+
+        let proof = key
+            .proof("get", &url, Some("token"), Some("nonce"))
+            .unwrap();
+
+        let header = decode_header(&proof).unwrap();
+        assert_eq!(header.typ.as_deref(), Some(DPOP_PROOF_TYPE));
+        let mut validation = Validation::new(Algorithm::ES256);
+        validation.required_spec_claims.clear();
+        let claims = decode::<DpopProofClaims>(
+            &proof,
+            &DecodingKey::from_jwk(&header.jwk.unwrap()).unwrap(),
+            &validation,
+        )
+        .unwrap()
+        .claims;
+        assert_eq!(claims.htm, "GET");
+        assert_eq!(claims.htu, "https://api.example.com/resource");
+        assert_eq!(claims.ath, Some(access_token_hash("token")));
+        assert_eq!(claims.nonce.as_deref(), Some("nonce"));
+    }
+}
@@ -1,29 +1,65 @@
+use std::sync::Arc;
+
 use anyhow::{Error, Ok};
-use axum_extra::extract::CookieJar;
-use chrono::{Duration, Utc};
+use axum_extra::extract::{cookie::SameSite, CookieJar};
+use chrono::{DateTime, Duration, Utc};
 use oauth2::TokenResponse;
 
-use super::OAuthConfig;
-use crate::cookies::new_cookie;
-use crate::session::{update_session, Session};
-use crate::{oauth::OAuthClient, settings::CookiesConfig};
+use serde_json::{Map, Value};
+
+use super::{
+    merge_userinfo, AccessToken, AuthorizationRequest, AuthorizationState, DpopKey, IdTokenClaims,
+    IntrospectedToken, OAuthConfig, TokenExchangeRequest, DEFAULT_TOKEN_TTL, DPOP_TOKEN_TYPE,
+};
+use crate::cookies::{new_cookie, remove_cookie};
+use crate::error::AuthorizationError;
+use crate::session::{
+    extract_session, map_claims, update_session, MemorySessionStore, ResourceToken, Session,
+    SessionStore,
+};
+use crate::{
+    oauth::OAuthClient,
+    settings::{CookieConfig, CookiesConfig, SessionConfig},
+};
+
+const DEFAULT_SESSION_LIFETIME: u64 = 24 * 3600;
 
 #[derive(Clone)]
 pub struct OAuthHttpHandler {
     client: OAuthClient,
     cookies_config: CookiesConfig,
+    session_config: SessionConfig,
+    session_store: Arc<dyn SessionStore>,
+    provider: Option<String>,
 }
 
 impl OAuthHttpHandler {
-    pub fn new(oauth_config: OAuthConfig, cookies_config: CookiesConfig) -> Result<Self, Error> {
-        let client = OAuthClient::new(oauth_config)?;
+    pub async fn new(
+        oauth_config: OAuthConfig,
+        cookies_config: CookiesConfig,
+        session_config: SessionConfig,
+    ) -> Result<Self, Error> {
+        let client = OAuthClient::new(oauth_config).await?;
 
         Ok(Self {
             client,
             cookies_config,
+            session_config,
+            session_store: Arc::new(MemorySessionStore::default()),
+            provider: None,
         })
     }
 
+    pub fn with_session_store(mut self, session_store: Arc<dyn SessionStore>) -> Self {
+        self.session_store = session_store;
+        self
+    }
+
+    pub fn with_provider(mut self, provider: Option<String>) -> Self {
+        self.provider = provider;
+        self
+    }
+
     fn get_access_token(&self, jar: &CookieJar) -> Result<Option<String>, Error> {
         let access_token = jar
             .get(self.cookies_config.access_token.name.as_str())
@@ -42,6 +78,83 @@ impl OAuthHttpHandler {
         &self.client
     }
 
+    // Name of the provider, none for the default provider
+    pub fn provider(&self) -> Option<&str> {
+        self.provider.as_deref()
+    }
+
+    /**
+     * State of the login in progress, stored in the CSRF cookie.
+     */
+    pub fn authorization_state(&self, jar: &CookieJar) -> Option<AuthorizationState> {
+        jar.get(self.cookies_config.oauth_csrf.name.as_str())
+            .and_then(|cookie| AuthorizationState::decode(cookie.value()).ok())
+    }
+
+    /**
+     * Get the session of the user, it must still be active in the session store.
+     */
+    pub async fn get_session(&self, jar: CookieJar) -> (CookieJar, Option<Session>) {
+        let session = match extract_session(&jar, &self.cookies_config.session) {
+            Result::Ok(Some(session)) => session,
+            Result::Ok(None) => return (jar, None),
+            Err(_) => {
+                return (
+                    jar.remove(remove_cookie(self.cookies_config.session.to_owned())),
+                    None,
+                )
+            }
+        };
+
+        match self.session_store.get(session.id()).await {
+            // The expired session is kept until it is swept, it tells the provider to refresh with
+            Some(session) if session.is_expired() => (jar, None),
+            Some(session) => (jar, Some(session)),
+            // Swept from the store once expired, the refresh token is kept
+            None if session.is_expired() => (jar, None),
+            // The session has been terminated, e.g. by a back-channel logout
+            None => (self.clear_session_cookies(jar), None),
+        }
+    }
+
+    /**
+     * Get the session of the user even when it has expired, none when it has been terminated.
+     */
+    pub async fn find_session(&self, jar: &CookieJar) -> Option<Session> {
+        let session = extract_session(jar, &self.cookies_config.session).ok()??;
+        match self.session_store.get(session.id()).await {
+            Some(session) => Some(session),
+            // The cookie tells a swept session apart from a terminated one
+            None if session.is_expired() => Some(session),
+            None => None,
+        }
+    }
+
+    async fn save_session(&self, jar: CookieJar, session: Session) -> CookieJar {
+        self.session_store.insert(session.clone()).await;
+        update_session(jar, self.cookies_config.session.to_owned(), Some(session))
+    }
+
+    // The session outlives the access tokens, which are refreshed within its lifetime
+    fn session_expire(&self) -> Option<DateTime<Utc>> {
+        let lifetime = self
+            .session_config
+            .lifetime
+            .unwrap_or(DEFAULT_SESSION_LIFETIME);
+        Utc::now().checked_add_signed(Duration::seconds(lifetime as i64))
+    }
+
+    pub fn dpop_key(&self, session: &Session) -> Result<Option<DpopKey>, Error> {
+        session.dpop_key().map(DpopKey::decode).transpose()
+    }
+
+    fn clear_session_cookies(&self, jar: CookieJar) -> CookieJar {
+        jar.remove(remove_cookie(self.cookies_config.access_token.to_owned()))
+            .remove(remove_cookie(self.cookies_config.refresh_token.to_owned()))
+            .remove(remove_cookie(self.cookies_config.id_token.to_owned()))
+            .remove(remove_cookie(self.cookies_config.session.to_owned()))
+    }
+
     pub async fn get_or_refresh_token(
         &self,
         jar: CookieJar,
@@ -51,39 +164,342 @@ impl OAuthHttpHandler {
             return Ok((jar, None));
         }
 
-        self.refresh_access_token(jar).await
+        if !self.client.is_introspection_enabled() {
+            return self.refresh_access_token(jar).await;
+        }
+
+        let (jar, session) = self.get_session(jar).await;
+        if session.is_none() {
+            return Ok((jar, None));
+        }
+
+        let introspected_token = self
+            .client
+            .introspect(access_token.as_ref().unwrap())
+            .await?;
+        if introspected_token.active {
+            return Ok((jar, access_token));
+        }
+
+        // Inactive tokens are only used again once refreshed
+        let (updated_jar, access_token) = self.refresh_access_token(jar).await?;
+        if access_token.is_none() {
+            return Err(AuthorizationError::InvalidToken(
+                "The access token is inactive".to_string(),
+            )
+            .into());
+        }
+
+        Ok((updated_jar, access_token))
     }
 
-    pub async fn refresh_access_token(
+    /**
+     * Introspect the access token when introspection is enabled, the session is updated with
+     * the introspected scope. A token of another subject than the session one is rejected.
+     */
+    pub async fn introspect(
         &self,
         jar: CookieJar,
+        access_token: &str,
+    ) -> Result<(CookieJar, Option<IntrospectedToken>), Error> {
+        let Some(introspected_token) = self.introspect_token(access_token).await? else {
+            return Ok((jar, None));
+        };
+        let (mut updated_jar, session) = self.get_session(jar).await;
+        if let Some(session) = session.filter(|_| introspected_token.active) {
+            if let (Some(sub), Some(session_sub)) = (&introspected_token.sub, session.sub()) {
+                if sub != session_sub {
+                    return Err(AuthorizationError::InvalidToken(
+                        "The access token subject does not match the session".to_string(),
+                    )
+                    .into());
+                }
+            }
+            let scopes = introspected_token
+                .scope
+                .clone()
+                .or(session.scopes().cloned());
+            let sub = session
+                .sub()
+                .map(String::from)
+                .or(introspected_token.sub.clone());
+            if scopes.as_ref() != session.scopes() || sub.as_deref() != session.sub() {
+                let sid = session.sid().map(String::from);
+                let session = session.with_scopes(scopes).with_identity(sub, sid);
+                updated_jar = self.save_session(updated_jar, session).await;
+            }
+        }
+
+        Ok((updated_jar, Some(introspected_token)))
+    }
+
+    /**
+     * Introspect a token without updating the session, e.g. a resource token.
+     */
+    pub async fn introspect_token(
+        &self,
+        access_token: &str,
+    ) -> Result<Option<IntrospectedToken>, Error> {
+        if !self.client.is_introspection_enabled() {
+            return Ok(None);
+        }
+
+        Ok(Some(self.client.introspect(access_token).await?))
+    }
+
+    /**
+     * Get the access token of the session restricted to `resource`. It is obtained with the
+     * refresh token the first time and once expired, then kept in the session.
+     */
+    pub async fn get_resource_token(
+        &self,
+        jar: CookieJar,
+        resource: &str,
     ) -> Result<(CookieJar, Option<String>), Error> {
-        let refresh_token = self.get_refresh_token(&jar)?;
-        if refresh_token.is_none() {
+        let (jar, session) = self.get_session(jar).await;
+        let Some(session) = session else {
+            return Ok((jar, None));
+        };
+        if let Some(token) = session
+            .resource_token(resource)
+            .filter(|token| !token.is_expired())
+        {
+            return Ok((jar, Some(token.access_token.clone())));
+        }
+
+        let Some(refresh_token) = self.get_refresh_token(&jar)? else {
             return Ok((jar, None));
+        };
+        let dpop_key = self.dpop_key(&session)?;
+        let token_result = self
+            .client
+            .refresh_token(refresh_token, dpop_key.as_ref(), Some(resource))
+            .await?;
+        let access_token = token_result.access_token().secret().to_string();
+
+        let mut updated_jar = jar;
+        // The refresh token may be rotated
+        if let Some(refresh_token) = token_result.refresh_token() {
+            updated_jar = updated_jar.add(new_cookie(
+                self.cookies_config.refresh_token.to_owned(),
+                refresh_token.secret().to_string(),
+            ));
         }
+        let session = session.with_resource_token(
+            resource.to_string(),
+            ResourceToken {
+                access_token: access_token.clone(),
+                exp: token_expire(token_result.expires_in()),
+                dpop: dpop_key.is_some() && OAuthClient::is_dpop_bound(&token_result),
+            },
+        );
+        updated_jar = self.save_session(updated_jar, session).await;
 
-        let token_result = self.client.refresh_token(refresh_token.unwrap()).await?;
+        Ok((updated_jar, Some(access_token)))
+    }
+
+    /**
+     * Get the token the session access token is exchanged for to call `upstream`. It is kept
+     * in the session until it expires.
+     */
+    pub async fn get_exchanged_token(
+        &self,
+        jar: CookieJar,
+        upstream: &str,
+        request: &TokenExchangeRequest,
+    ) -> Result<(CookieJar, Option<String>), Error> {
+        let (jar, session) = self.get_session(jar).await;
+        if let Some(token) = session
+            .as_ref()
+            .and_then(|session| session.exchanged_token(upstream))
+            .filter(|token| !token.is_expired())
+        {
+            return Ok((jar, Some(token.access_token.clone())));
+        }
+
+        let (jar, subject_token) = self.get_or_refresh_token(jar).await?;
+        let Some(subject_token) = subject_token else {
+            return Ok((jar, None));
+        };
+        // The session may have been updated by the token refresh
+        let (jar, session) = self.get_session(jar).await;
+        let Some(session) = session else {
+            return Ok((jar, None));
+        };
+
+        // The subject token is bound to the DPoP key of the session
+        let dpop_key = self.dpop_key(&session)?;
+        let response = self
+            .client
+            .exchange_token(&subject_token, request, dpop_key.as_ref())
+            .await?;
+        let session = session.with_exchanged_token(
+            upstream.to_string(),
+            ResourceToken {
+                access_token: response.access_token.clone(),
+                exp: token_expire(response.expires_in.map(std::time::Duration::from_secs)),
+                dpop: dpop_key.is_some()
+                    && response.token_type.eq_ignore_ascii_case(DPOP_TOKEN_TYPE),
+            },
+        );
+        let updated_jar = self.save_session(jar, session).await;
+
+        Ok((updated_jar, Some(response.access_token)))
+    }
+
+    pub async fn refresh_access_token(
+        &self,
+        jar: CookieJar,
+    ) -> Result<(CookieJar, Option<String>), Error> {
+        // An expired session is renewed with the refresh token, a terminated one is not
+        let (jar, session) = self.get_session(jar).await;
+        let expired_session = match session {
+            Some(_) => None,
+            None => self.find_session(&jar).await,
+        };
+        let Some(refresh_token) = self.get_refresh_token(&jar)? else {
+            return Ok((jar, None));
+        };
+
+        let dpop_key = match session.as_ref().or(expired_session.as_ref()) {
+            Some(session) => self.dpop_key(session)?,
+            None => None,
+        };
+        let token_result = self
+            .client
+            .refresh_token(refresh_token, dpop_key.as_ref(), None)
+            .await?;
         let token = token_result.access_token();
-        let updated_jar = jar.add(new_cookie(
+        let mut updated_jar = jar.add(new_cookie(
             self.cookies_config.access_token.to_owned(),
             token.secret().to_string(),
         ));
+        // The refresh token may be rotated
+        if let Some(refresh_token) = token_result.refresh_token() {
+            updated_jar = updated_jar.add(new_cookie(
+                self.cookies_config.refresh_token.to_owned(),
+                refresh_token.secret().to_string(),
+            ));
+        }
+        if let Some(id_token) = &token_result.extra_fields().id_token {
+            updated_jar = updated_jar.add(new_cookie(
+                self.cookies_config.id_token.to_owned(),
+                id_token.to_string(),
+            ));
+        }
+
+        let session = match session {
+            Some(session) => session,
+            None => {
+                if let Some(expired_session) = &expired_session {
+                    self.session_store.remove(expired_session.id()).await;
+                }
+                self.refreshed_session(&token_result, expired_session.as_ref())
+                    .await?
+            }
+        };
+        let scopes = granted_scopes(&token_result).or(session.scopes().cloned());
+        let mut session = session.with_scopes(scopes);
+        if self.client.config().refresh_userinfo.unwrap_or(false) {
+            let userinfo = self
+                .client
+                .fetch_userinfo(token.secret(), dpop_key.as_ref())
+                .await?;
+            let claims = merge_userinfo(
+                session.claims().clone(),
+                map_claims(&userinfo, &self.session_config),
+            )?;
+            session = session.with_claims(claims);
+        }
+        updated_jar = self.save_session(updated_jar, session).await;
+
         Ok((updated_jar, Some(token.secret().to_string())))
     }
 
-    pub fn authorize(&self, jar: CookieJar, scope: Option<Vec<String>>) -> (CookieJar, String) {
-        let (url, csrf_token, pkce_code_verifier) = self.client.build_authorization_endpoint(scope);
+    pub async fn get_userinfo(
+        &self,
+        jar: CookieJar,
+    ) -> Result<(CookieJar, Option<Map<String, Value>>), Error> {
+        let (updated_jar, access_token) = self.get_or_refresh_token(jar).await?;
+        match access_token {
+            Some(access_token) => {
+                let (updated_jar, session) = self.get_session(updated_jar).await;
+                let dpop_key = match session {
+                    Some(session) => self.dpop_key(&session)?,
+                    None => None,
+                };
+                let userinfo = self
+                    .client
+                    .fetch_userinfo(&access_token, dpop_key.as_ref())
+                    .await?;
+                Ok((updated_jar, Some(userinfo)))
+            }
+            None => Ok((updated_jar, None)),
+        }
+    }
+
+    pub async fn authorize(
+        &self,
+        jar: CookieJar,
+        authorization_request: &AuthorizationRequest,
+    ) -> Result<(CookieJar, String), Error> {
+        let (jar, session) = self.get_session(jar).await;
+        let scope = authorization_request.scopes(
+            self.client
+                .config()
+                .default_scopes
+                .as_deref()
+                .unwrap_or_default(),
+            session.as_ref().and_then(Session::scopes),
+        );
+        let authorization_request = AuthorizationRequest {
+            scope: Some(scope),
+            ..authorization_request.clone()
+        };
+        let (url, csrf_token, pkce_code_verifier, nonce) = self
+            .client
+            .build_authorization_endpoint(&authorization_request)
+            .await?;
+        // The issuer is kept with the state to be checked against the authorization response
+        let state = AuthorizationState::new(
+            csrf_token.secret().to_string(),
+            self.client.metadata().await.issuer,
+        )
+        .with_return_to(authorization_request.return_to.clone())
+        .with_silent(authorization_request.is_silent())
+        .with_scope(authorization_request.scope)
+        .with_provider(self.provider.clone());
         let updated_jar = jar
             .add(new_cookie(
-                self.cookies_config.oauth_csrf.to_owned(),
-                csrf_token.secret().to_string(),
+                self.authorization_cookie(&self.cookies_config.oauth_csrf),
+                state.encode(),
             ))
             .add(new_cookie(
-                self.cookies_config.oauth_pkce.to_owned(),
+                self.authorization_cookie(&self.cookies_config.oauth_pkce),
                 pkce_code_verifier.secret().to_string(),
+            ))
+            .add(new_cookie(
+                self.authorization_cookie(&self.cookies_config.oauth_nonce),
+                nonce,
             ));
-        (updated_jar, url.to_string())
+        Ok((updated_jar, url.to_string()))
+    }
+
+    /**
+     * The form post response modes send the authorization response in a cross-site POST, the
+     * cookies of the login must then be sent with cross-site requests.
+     */
+    fn authorization_cookie(&self, config: &CookieConfig) -> CookieConfig {
+        let response_mode = self.client.config().response_mode.unwrap_or_default();
+        if !response_mode.is_form_post() {
+            return config.to_owned();
+        }
+
+        CookieConfig {
+            same_site: SameSite::None,
+            secure: true,
+            ..config.to_owned()
+        }
     }
 
     pub async fn exchange_code(
@@ -91,10 +507,28 @@ impl OAuthHttpHandler {
         jar: CookieJar,
         code: String,
         pkce_verifier: String,
+        nonce: String,
+        requested_scopes: Option<Vec<String>>,
     ) -> Result<CookieJar, Error> {
-        let token_result = self.client.exchange_code(code, pkce_verifier).await?;
+        let dpop_key = if self.client.is_dpop_enabled() {
+            Some(DpopKey::generate()?)
+        } else {
+            None
+        };
+        let token_result = self
+            .client
+            .exchange_code(code, pkce_verifier, dpop_key.as_ref())
+            .await?;
+        let dpop_key = dpop_key.filter(|_| OAuthClient::is_dpop_bound(&token_result));
+        let (id_token_claims, claims) = self
+            .identity_claims(&token_result, &nonce, dpop_key.as_ref())
+            .await?;
         let token = token_result.access_token();
 
+        // A new login, e.g. a step-up, replaces the previous session of the browser
+        if let Result::Ok(Some(previous)) = extract_session(&jar, &self.cookies_config.session) {
+            self.session_store.remove(previous.id()).await;
+        }
         let mut updated_jar = jar.add(new_cookie(
             self.cookies_config.access_token.to_owned(),
             token.secret().to_string(),
@@ -105,28 +539,244 @@ impl OAuthHttpHandler {
                 token_result.refresh_token().unwrap().secret().to_string(),
             ));
         } else {
-            updated_jar = updated_jar.remove(self.cookies_config.refresh_token.to_owned().name);
-        }
-
-        let now = Utc::now();
-        let expires_in = token_result.expires_in().map(|duration| {
-            now.checked_add_signed(Duration::from_std(duration).unwrap())
-                .unwrap()
-        });
-        let session = Session::new(None, Some(now), expires_in);
-        updated_jar = update_session(
-            updated_jar,
-            self.cookies_config.session.to_owned(),
-            Some(session),
-        );
+            updated_jar =
+                updated_jar.remove(remove_cookie(self.cookies_config.refresh_token.to_owned()));
+        }
+        if let Some(id_token) = &token_result.extra_fields().id_token {
+            updated_jar = updated_jar.add(new_cookie(
+                self.cookies_config.id_token.to_owned(),
+                id_token.to_string(),
+            ));
+        } else {
+            updated_jar =
+                updated_jar.remove(remove_cookie(self.cookies_config.id_token.to_owned()));
+        }
+
+        let session = Session::new(None, Some(Utc::now()), self.session_expire());
+        let session = with_id_token_identity(session, id_token_claims.as_ref())
+            .with_scopes(granted_scopes(&token_result).or(requested_scopes))
+            .with_claims(map_claims(&claims, &self.session_config))
+            .with_dpop_key(dpop_key.map(|dpop_key| dpop_key.encode()))
+            .with_provider(self.provider.clone());
+        updated_jar = self.save_session(updated_jar, session).await;
 
         Ok(updated_jar)
     }
 
-    pub fn clone(&self) -> Self {
-        Self {
-            client: self.client.clone(),
-            cookies_config: self.cookies_config.clone(),
+    /**
+     * Revoke the refresh and access tokens of the user at the provider.
+     */
+    pub async fn revoke_tokens(&self, jar: &CookieJar) -> Result<(), Error> {
+        if let Some(refresh_token) = self.get_refresh_token(jar)? {
+            self.client.revoke_refresh_token(refresh_token).await?;
         }
+        if let Some(access_token) = self.get_access_token(jar)? {
+            self.client.revoke_access_token(access_token).await?;
+        }
+
+        Ok(())
     }
+
+    /**
+     * Revoke the tokens when the provider supports it and clear the tokens and the session of the user.
+     */
+    pub async fn terminate_session(&self, jar: CookieJar) -> CookieJar {
+        if self.client.metadata().await.revocation_endpoint.is_some() {
+            // The session is terminated even when the provider fails to revoke the tokens
+            let _ = self.revoke_tokens(&jar).await;
+        }
+
+        if let Result::Ok(Some(session)) = extract_session(&jar, &self.cookies_config.session) {
+            self.session_store.remove(session.id()).await;
+        }
+
+        self.clear_session_cookies(jar)
+    }
+
+    /**
+     * Terminate the sessions of the provider session `sid` when the provider loads the front-channel
+     * logout uri. The session cookies are only cleared when the browser sends them in the iframe,
+     * which requires `SameSite=None` cookies, the sessions are removed from the store regardless.
+     * Front-Channel Logout: https://openid.net/specs/openid-connect-frontchannel-1_0.html
+     */
+    pub async fn frontchannel_logout(
+        &self,
+        jar: CookieJar,
+        iss: Option<String>,
+        sid: Option<String>,
+    ) -> Result<CookieJar, Error> {
+        // The sid is only unique for the issuer, both are sent together
+        match iss {
+            Some(iss) if self.client.metadata().await.issuer.as_ref() != Some(&iss) => {
+                return Err(Error::msg("Issuer mismatch"));
+            }
+            None if sid.is_some() => return Err(Error::msg("Issuer not found")),
+            _ => {}
+        }
+
+        let (jar, session) = self.get_session(jar).await;
+        let Some(sid) = sid else {
+            return Ok(self.terminate_session(jar).await);
+        };
+
+        let provider = self.provider.as_deref();
+        self.session_store.remove_by_sid(provider, &sid).await;
+        if session.is_some_and(|session| {
+            session.provider() == provider && session.sid() == Some(sid.as_str())
+        }) {
+            return Ok(self.terminate_session(jar).await);
+        }
+
+        Ok(jar)
+    }
+
+    /**
+     * Terminate the sessions designated by a back-channel logout token.
+     * Back-Channel Logout: https://openid.net/specs/openid-connect-backchannel-1_0.html
+     */
+    pub async fn backchannel_logout(&self, logout_token: &str) -> Result<(), Error> {
+        let claims = self.client.validate_logout_token(logout_token).await?;
+        let provider = self.provider.as_deref();
+        match (claims.sid, claims.sub) {
+            (Some(sid), _) => self.session_store.remove_by_sid(provider, &sid).await,
+            (None, Some(sub)) => self.session_store.remove_by_sub(provider, &sub).await,
+            (None, None) => return Err(Error::msg("Logout token must contain sid or sub")),
+        };
+
+        Ok(())
+    }
+
+    /**
+     * Terminate the session and, when the provider supports it, return the end session url
+     * the user must be redirected to.
+     */
+    pub async fn logout(&self, jar: CookieJar) -> (CookieJar, Option<String>) {
+        let id_token = jar
+            .get(self.cookies_config.id_token.name.as_str())
+            .map(|cookie| cookie.value().to_string());
+        let updated_jar = self.terminate_session(jar).await;
+
+        match self
+            .client
+            .build_end_session_endpoint(id_token.as_deref())
+            .await
+        {
+            Some((url, Some(state))) => (
+                updated_jar.add(new_cookie(
+                    self.cookies_config.oauth_csrf.to_owned(),
+                    state.secret().to_string(),
+                )),
+                Some(url.to_string()),
+            ),
+            Some((url, None)) => (updated_jar, Some(url.to_string())),
+            None => (updated_jar, None),
+        }
+    }
+
+    /**
+     * New session started with a refresh token once the previous one expired. It carries over the
+     * identity and the DPoP key of the expired session, the identity being updated by the ID token
+     * of the refresh response when there is one. Once the expired session has been swept from the
+     * store only its cookie remains, which does not hold the DPoP key: the new session is not DPoP
+     * bound and a refresh token bound to the lost key is rejected by the provider.
+     */
+    async fn refreshed_session(
+        &self,
+        token_result: &AccessToken,
+        expired_session: Option<&Session>,
+    ) -> Result<Session, Error> {
+        let mut session = Session::new(None, Some(Utc::now()), self.session_expire())
+            .with_provider(self.provider.clone());
+        if let Some(expired_session) = expired_session {
+            session = session
+                .with_identity(
+                    expired_session.sub().map(String::from),
+                    expired_session.sid().map(String::from),
+                )
+                .with_authentication(
+                    expired_session.acr().map(String::from),
+                    expired_session.auth_time(),
+                )
+                .with_scopes(expired_session.scopes().cloned())
+                .with_claims(expired_session.claims().clone())
+                .with_dpop_key(expired_session.dpop_key().map(String::from));
+        }
+        let Some(id_token) = token_result.extra_fields().id_token.as_deref() else {
+            return Ok(session);
+        };
+
+        let claims = self.client.validate_id_token(id_token, None).await?;
+        // The subject of a refreshed ID token must not change
+        if session.sub().is_some_and(|sub| sub != claims.sub) {
+            return Err(Error::msg("Subject mismatch"));
+        }
+        let session = with_id_token_identity(session, Some(&claims));
+        let claims = match serde_json::to_value(claims)? {
+            Value::Object(claims) => claims,
+            _ => Map::new(),
+        };
+
+        Ok(session.with_claims(map_claims(&claims, &self.session_config)))
+    }
+
+    /**
+     * Validated ID token claims, which the session identity is taken from, and the claims to
+     * display, the ID token ones merged with userinfo.
+     */
+    async fn identity_claims(
+        &self,
+        token_result: &AccessToken,
+        nonce: &str,
+        dpop_key: Option<&DpopKey>,
+    ) -> Result<(Option<IdTokenClaims>, Map<String, Value>), Error> {
+        let id_token_claims = match token_result.extra_fields().id_token.as_deref() {
+            Some(id_token) => Some(self.client.validate_id_token(id_token, Some(nonce)).await?),
+            None => None,
+        };
+        let mut claims = match serde_json::to_value(&id_token_claims)? {
+            Value::Object(claims) => claims,
+            _ => Map::new(),
+        };
+
+        let fetch_userinfo = self.client.config().fetch_userinfo.unwrap_or(true);
+        if fetch_userinfo && self.client.metadata().await.userinfo_endpoint.is_some() {
+            let userinfo = self
+                .client
+                .fetch_userinfo(token_result.access_token().secret(), dpop_key)
+                .await?;
+            claims = merge_userinfo(claims, userinfo)?;
+        }
+
+        Ok((id_token_claims, claims))
+    }
+}
+
+// Userinfo is not signed by default, the identity and authentication only come from the ID token
+fn with_id_token_identity(session: Session, claims: Option<&IdTokenClaims>) -> Session {
+    let Some(claims) = claims else {
+        return session;
+    };
+    let auth_time = claims
+        .auth_time
+        .and_then(|auth_time| DateTime::from_timestamp(auth_time, 0));
+
+    session
+        .with_identity(Some(claims.sub.clone()), claims.sid.clone())
+        .with_authentication(claims.acr.clone(), auth_time)
+}
+
+// The token response only lists the scopes when they differ from the requested ones
+fn granted_scopes(token_result: &AccessToken) -> Option<Vec<String>> {
+    token_result
+        .scopes()
+        .map(|scopes| scopes.iter().map(|scope| scope.to_string()).collect())
+}
+
+// Tokens issued without expiration are renewed after the default lifetime
+fn token_expire(expires_in: Option<std::time::Duration>) -> DateTime<Utc> {
+    let expires_in = expires_in.unwrap_or(DEFAULT_TOKEN_TTL);
+    Duration::from_std(expires_in)
+        .ok()
+        .and_then(|expires_in| Utc::now().checked_add_signed(expires_in))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
 }
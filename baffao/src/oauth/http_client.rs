@@ -0,0 +1,146 @@
+use std::fmt;
+
+use oauth2::{
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    HttpRequest, HttpResponse,
+};
+use serde::Deserialize;
+
+use super::dpop::{DpopKey, DpopNonces, DPOP_HEADER, DPOP_NONCE_HEADER};
+
+#[derive(Debug)]
+pub enum HttpClientError {
+    Request(reqwest::Error),
+    Dpop(anyhow::Error),
+}
+
+impl fmt::Display for HttpClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HttpClientError::Request(e) => write!(f, "{}", e),
+            HttpClientError::Dpop(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for HttpClientError {}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+impl From<reqwest::Error> for HttpClientError {
+    fn from(e: reqwest::Error) -> Self {
+        HttpClientError::Request(e)
+    }
+}
+
+/**
+ * Send the requests of the oauth2 client through our own HTTP client so that they share its
+ * TLS configuration.
+*/
+pub async fn send_request(
+    http_client: &reqwest::Client,
+    request: HttpRequest,
+) -> Result<HttpResponse, HttpClientError> {
+    // oauth2 only uses standard methods, which always convert
+    let method = reqwest::Method::from_bytes(request.method.as_str().as_bytes())
+        .unwrap_or(reqwest::Method::POST);
+    let mut builder = http_client
+        .request(method, request.url.as_str())
+        .body(request.body);
+    for (name, value) in request.headers.iter() {
+        builder = builder.header(name.as_str(), value.as_bytes());
+    }
+
+    let response = builder.send().await?;
+    let status_code = StatusCode::from_u16(response.status().as_u16())
+        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| {
+            Some((
+                HeaderName::from_bytes(name.as_str().as_bytes()).ok()?,
+                HeaderValue::from_bytes(value.as_bytes()).ok()?,
+            ))
+        })
+        .collect::<HeaderMap>();
+    let body = response.bytes().await?.to_vec();
+
+    Ok(HttpResponse {
+        status_code,
+        headers,
+        body,
+    })
+}
+
+/**
+ * Send the request with a DPoP proof. When the server requires a new nonce, the request is
+ * sent again once with a proof including it.
+*/
+pub async fn send_dpop_request(
+    http_client: &reqwest::Client,
+    request: HttpRequest,
+    dpop_key: &DpopKey,
+    dpop_nonces: &DpopNonces,
+) -> Result<HttpResponse, HttpClientError> {
+    let mut retried = false;
+    loop {
+        let nonce = dpop_nonces.get(&request.url);
+        let proof = dpop_key
+            .proof(
+                request.method.as_str(),
+                &request.url,
+                None,
+                nonce.as_deref(),
+            )
+            .map_err(HttpClientError::Dpop)?;
+        let mut dpop_request = request.clone();
+        dpop_request.headers.insert(
+            DPOP_HEADER,
+            HeaderValue::from_str(&proof).map_err(|e| HttpClientError::Dpop(e.into()))?,
+        );
+
+        let response = send_request(http_client, dpop_request).await?;
+        let new_nonce = response
+            .headers
+            .get(DPOP_NONCE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+        let Some(new_nonce) = new_nonce.filter(|new_nonce| Some(new_nonce) != nonce.as_ref())
+        else {
+            return Ok(response);
+        };
+        dpop_nonces.insert(&request.url, new_nonce);
+
+        let use_dpop_nonce = response.status_code == StatusCode::BAD_REQUEST
+            && is_use_dpop_nonce_error(&response.body);
+        if !use_dpop_nonce || retried {
+            return Ok(response);
+        }
+        retried = true;
+    }
+}
+
+pub fn is_use_dpop_nonce_error(body: &[u8]) -> bool {
+    serde_json::from_slice::<ErrorResponse>(body)
+        .is_ok_and(|response| response.error == "use_dpop_nonce")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_use_dpop_nonce_error() {
+        assert!(is_use_dpop_nonce_error(
+            br#"{"error":"use_dpop_nonce","error_description":"Nonce required"}"#
+        ));
+        assert!(!is_use_dpop_nonce_error(
+            br#"{"error":"invalid_grant","error_description":"use_dpop_nonce"}"#
+        ));
+        assert!(!is_use_dpop_nonce_error(b"use_dpop_nonce"));
+    }
+}
@@ -0,0 +1,213 @@
+use anyhow::Error;
+use chrono::Utc;
+use jsonwebtoken::{decode, errors::ErrorKind, Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum Audience {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl Audience {
+    pub fn contains(&self, value: &str) -> bool {
+        match self {
+            Audience::Single(aud) => aud == value,
+            Audience::Multiple(aud) => aud.iter().any(|aud| aud == value),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Audience::Single(_) => 1,
+            Audience::Multiple(aud) => aud.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/**
+ * IdTokenClaims
+ *
+ * ID Token: https://openid.net/specs/openid-connect-core-1_0.html#IDToken
+*/
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: Audience,
+    pub exp: i64,
+    pub iat: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub azp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acr: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amr: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    #[serde(flatten)]
+    pub additional_claims: Map<String, Value>,
+}
+
+pub struct IdTokenValidation<'a> {
+    pub issuer: &'a str,
+    pub client_id: &'a str,
+    pub nonce: Option<&'a str>,
+    pub clock_skew: u64,
+}
+
+/**
+ * Validate the ID token signature and claims.
+ * ID Token Validation: https://openid.net/specs/openid-connect-core-1_0.html#IDTokenValidation
+*/
+pub fn validate_id_token(
+    id_token: &str,
+    key: &DecodingKey,
+    alg: Algorithm,
+    expected: &IdTokenValidation,
+) -> Result<IdTokenClaims, Error> {
+    let mut validation = Validation::new(alg);
+    validation.leeway = expected.clock_skew;
+    validation.set_issuer(&[expected.issuer]);
+    validation.set_audience(&[expected.client_id]);
+    validation.set_required_spec_claims(&["iss", "sub", "aud", "exp", "iat"]);
+
+    let claims = decode::<IdTokenClaims>(id_token, key, &validation)
+        .map_err(|e| match e.kind() {
+            ErrorKind::InvalidSignature => Error::msg("Invalid ID token signature"),
+            ErrorKind::ExpiredSignature => Error::msg("ID token has expired"),
+            ErrorKind::InvalidIssuer => Error::msg("ID token issuer mismatch"),
+            ErrorKind::InvalidAudience => Error::msg("ID token audience mismatch"),
+            _ => Error::msg(format!("Invalid ID token: {}", e)),
+        })?
+        .claims;
+
+    if claims.iat > Utc::now().timestamp() + expected.clock_skew as i64 {
+        return Err(Error::msg("ID token issued in the future"));
+    }
+
+    if claims.aud.len() > 1 && claims.azp.is_none() {
+        return Err(Error::msg("ID token authorized party missing"));
+    }
+    if claims
+        .azp
+        .as_ref()
+        .is_some_and(|azp| azp != expected.client_id)
+    {
+        return Err(Error::msg("ID token authorized party mismatch"));
+    }
+
+    if let Some(nonce) = expected.nonce {
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(Error::msg("ID token nonce mismatch"));
+        }
+    }
+
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+
+    use super::*;
+
+    const SECRET: &[u8] = b"secret";
+
+    fn sign(claims: Value) -> String {
+        encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(SECRET),
+        )
+        .unwrap()
+    }
+
+    fn claims() -> Value {
+        let now = Utc::now().timestamp();
+        json!({
+            "iss": "https://issuer.example.com",
+            "sub": "user",
+            "aud": "client",
+            "exp": now + 60,
+            "iat": now,
+            "nonce": "nonce",
+        })
+    }
+
+    fn validate(id_token: &str) -> Result<IdTokenClaims, Error> {
+        validate_id_token(
+            id_token,
+            &DecodingKey::from_secret(SECRET),
+            Algorithm::HS256,
+            &IdTokenValidation {
+                issuer: "https://issuer.example.com",
+                client_id: "client",
+                nonce: Some("nonce"),
+                clock_skew: 0,
+            },
+        )
+    }
+
+    #[test]
+    fn test_validate_id_token() {
+        let claims = validate(&sign(claims())).unwrap();
+
+        assert_eq!(claims.sub, "user");
+        assert_eq!(claims.nonce.as_deref(), Some("nonce"));
+    }
+
+    #[test]
+    fn test_validate_id_token_rejects_invalid_claims() {
+        let now = Utc::now().timestamp();
+        let cases = [
+            (
+                "iss",
+                json!("https://other.example.com"),
+                "ID token issuer mismatch",
+            ),
+            ("aud", json!("other"), "ID token audience mismatch"),
+            ("exp", json!(now - 60), "ID token has expired"),
+            ("iat", json!(now + 60), "ID token issued in the future"),
+            ("nonce", json!("other"), "ID token nonce mismatch"),
+            (
+                "aud",
+                json!(["client", "other"]),
+                "ID token authorized party missing",
+            ),
+            ("azp", json!("other"), "ID token authorized party mismatch"),
+        ];
+
+        for (claim, value, message) in cases {
+            let mut claims = claims();
+            claims[claim] = value;
+
+            let error = validate(&sign(claims)).unwrap_err();
+            assert_eq!(error.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn test_validate_id_token_rejects_invalid_signature() {
+        let id_token = encode(
+            &Header::new(Algorithm::HS256),
+            &claims(),
+            &EncodingKey::from_secret(b"other"),
+        )
+        .unwrap();
+
+        let error = validate(&id_token).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid ID token signature");
+    }
+}
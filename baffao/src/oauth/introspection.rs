@@ -0,0 +1,117 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use oauth2::{basic::BasicTokenIntrospectionResponse, TokenIntrospectionResponse};
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+
+/**
+ * IntrospectedToken
+ *
+ * Token Introspection: https://datatracker.ietf.org/doc/html/rfc7662
+*/
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IntrospectedToken {
+    pub active: bool,
+    pub scope: Option<Vec<String>>,
+    pub sub: Option<String>,
+    pub exp: Option<DateTime<Utc>>,
+    pub client_id: Option<String>,
+}
+
+impl From<BasicTokenIntrospectionResponse> for IntrospectedToken {
+    fn from(response: BasicTokenIntrospectionResponse) -> Self {
+        Self {
+            active: response.active(),
+            scope: response
+                .scopes()
+                .map(|scopes| scopes.iter().map(|scope| scope.to_string()).collect()),
+            sub: response.sub().map(String::from),
+            exp: response.exp(),
+            client_id: response.client_id().map(|client_id| client_id.to_string()),
+        }
+    }
+}
+
+/**
+ * IntrospectionCache
+ *
+ * Short-lived cache of introspection results, keyed by the token hash so that tokens
+ * are not kept in memory.
+*/
+#[derive(Clone, Default)]
+pub struct IntrospectionCache {
+    entries: Arc<RwLock<HashMap<String, (IntrospectedToken, Instant)>>>,
+}
+
+impl IntrospectionCache {
+    pub fn get(&self, token: &str) -> Option<IntrospectedToken> {
+        let entries = self.entries.read().unwrap();
+        entries
+            .get(&token_hash(token))
+            .filter(|(_, expires_at)| *expires_at > Instant::now())
+            .map(|(introspected_token, _)| introspected_token.clone())
+    }
+
+    pub fn insert(&self, token: &str, introspected_token: IntrospectedToken, ttl: Duration) {
+        // An active token must not stay in the cache after it expires
+        let ttl = introspected_token
+            .exp
+            .filter(|_| introspected_token.active)
+            .map(|exp| (exp - Utc::now()).to_std().unwrap_or_default())
+            .map_or(ttl, |expires_in| expires_in.min(ttl));
+
+        let now = Instant::now();
+        let mut entries = self.entries.write().unwrap();
+        entries.retain(|_, (_, expires_at)| *expires_at > now);
+        entries.insert(token_hash(token), (introspected_token, now + ttl));
+    }
+}
+
+fn token_hash(token: &str) -> String {
+    hex::encode(digest(&SHA256, token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn introspected_token(active: bool, exp: Option<DateTime<Utc>>) -> IntrospectedToken {
+        IntrospectedToken {
+            active,
+            scope: Some(vec!["openid".to_string()]),
+            sub: Some("user".to_string()),
+            exp,
+            client_id: None,
+        }
+    }
+
+    #[test]
+    fn test_introspection_cache() {
+        let cache = IntrospectionCache::default();
+        cache.insert(
+            "token",
+            introspected_token(true, None),
+            Duration::from_secs(60),
+        );
+
+        assert!(cache.get("token").unwrap().active);
+        assert!(cache.get("other").is_none());
+    }
+
+    #[test]
+    fn test_introspection_cache_expires_with_token() {
+        let cache = IntrospectionCache::default();
+        cache.insert(
+            "token",
+            introspected_token(true, Some(Utc::now())),
+            Duration::from_secs(60),
+        );
+
+        assert!(cache.get("token").is_none());
+    }
+}
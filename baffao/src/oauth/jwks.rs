@@ -0,0 +1,116 @@
+use std::{
+    str::FromStr,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Error};
+use jsonwebtoken::{
+    jwk::{Jwk, KeyAlgorithm, PublicKeyUse},
+    Algorithm,
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+// Unknown key ids trigger a refetch of the JWKS, this limits how often it can happen
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize)]
+struct RawJwkSet {
+    keys: Vec<Value>,
+}
+
+#[derive(Default)]
+struct CachedJwks {
+    keys: Vec<Jwk>,
+    fetched_at: Option<Instant>,
+}
+
+/**
+ * JwksCache
+ *
+ * Keeps the provider signing keys in memory, the key set is fetched again
+ * when a token is signed with an unknown key to follow key rotations.
+*/
+#[derive(Clone, Default)]
+pub struct JwksCache {
+    cache: Arc<RwLock<CachedJwks>>,
+}
+
+impl JwksCache {
+    pub async fn find(
+        &self,
+        http_client: &reqwest::Client,
+        jwks_uri: &str,
+        kid: Option<&str>,
+        alg: Algorithm,
+    ) -> Result<Jwk, Error> {
+        if let Some(jwk) = self.lookup(kid, alg) {
+            return Ok(jwk);
+        }
+
+        if self.refresh(http_client, jwks_uri).await? {
+            if let Some(jwk) = self.lookup(kid, alg) {
+                return Ok(jwk);
+            }
+        }
+
+        Err(Error::msg(match kid {
+            Some(kid) => format!("Unknown signing key {}", kid),
+            None => "No signing key found".to_string(),
+        }))
+    }
+
+    fn lookup(&self, kid: Option<&str>, alg: Algorithm) -> Option<Jwk> {
+        let key_algorithm = KeyAlgorithm::from_str(&format!("{:?}", alg)).ok();
+        let cache = self.cache.read().unwrap();
+        let mut candidates = cache.keys.iter().filter(|jwk| {
+            let common = &jwk.common;
+            kid.is_none_or(|kid| common.key_id.as_deref() == Some(kid))
+                && !matches!(common.public_key_use, Some(PublicKeyUse::Encryption))
+                && common
+                    .key_algorithm
+                    .is_none_or(|algorithm| Some(algorithm) == key_algorithm)
+        });
+
+        let jwk = candidates.next()?;
+        // Without key id, the key must be unambiguous
+        if kid.is_none() && candidates.next().is_some() {
+            return None;
+        }
+
+        Some(jwk.clone())
+    }
+
+    async fn refresh(&self, http_client: &reqwest::Client, jwks_uri: &str) -> Result<bool, Error> {
+        {
+            let mut cache = self.cache.write().unwrap();
+            if cache
+                .fetched_at
+                .is_some_and(|fetched_at| fetched_at.elapsed() < MIN_REFRESH_INTERVAL)
+            {
+                return Ok(false);
+            }
+            cache.fetched_at = Some(Instant::now());
+        }
+
+        let raw_jwks = http_client
+            .get(jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<RawJwkSet>()
+            .await
+            .context("Failed to parse JWKS")?;
+
+        // Keys which are not supported are ignored instead of failing the whole set
+        let keys = raw_jwks
+            .keys
+            .into_iter()
+            .filter_map(|key| serde_json::from_value::<Jwk>(key).ok())
+            .collect();
+        self.cache.write().unwrap().keys = keys;
+
+        Ok(true)
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use anyhow::Error;
+use chrono::Utc;
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use super::Audience;
+
+const BACKCHANNEL_LOGOUT_EVENT: &str = "http://schemas.openid.net/event/backchannel-logout";
+
+/**
+ * LogoutTokenClaims
+ *
+ * Logout Token: https://openid.net/specs/openid-connect-backchannel-1_0.html#LogoutToken
+*/
+#[derive(Deserialize, Clone, Debug)]
+pub struct LogoutTokenClaims {
+    pub iss: String,
+    pub aud: Audience,
+    pub iat: i64,
+    pub exp: i64,
+    pub jti: String,
+    pub sub: Option<String>,
+    pub sid: Option<String>,
+    pub events: Map<String, Value>,
+    pub nonce: Option<Value>,
+}
+
+pub fn validate_logout_token(
+    logout_token: &str,
+    key: &DecodingKey,
+    alg: Algorithm,
+    issuer: &str,
+    client_id: &str,
+    clock_skew: u64,
+) -> Result<LogoutTokenClaims, Error> {
+    let mut validation = Validation::new(alg);
+    validation.leeway = clock_skew;
+    validation.set_issuer(&[issuer]);
+    validation.set_audience(&[client_id]);
+    validation.set_required_spec_claims(&["iss", "aud", "iat", "exp"]);
+
+    let claims = decode::<LogoutTokenClaims>(logout_token, key, &validation)
+        .map_err(|e| Error::msg(format!("Invalid logout token: {}", e)))?
+        .claims;
+
+    if !claims
+        .events
+        .get(BACKCHANNEL_LOGOUT_EVENT)
+        .is_some_and(Value::is_object)
+    {
+        return Err(Error::msg(
+            "Logout token is missing the back-channel logout event",
+        ));
+    }
+    if claims.sub.is_none() && claims.sid.is_none() {
+        return Err(Error::msg("Logout token must contain sid or sub"));
+    }
+    // A nonce would allow to use an ID token as a logout token
+    if claims.nonce.is_some() {
+        return Err(Error::msg("Logout token must not contain a nonce"));
+    }
+
+    Ok(claims)
+}
+
+/**
+ * LogoutTokenReplayCache
+ *
+ * The `jti` of the logout tokens already accepted, kept until the tokens expire so that a
+ * captured logout token cannot be replayed.
+*/
+#[derive(Clone, Default)]
+pub struct LogoutTokenReplayCache {
+    seen: Arc<RwLock<HashMap<(String, String), i64>>>,
+}
+
+impl LogoutTokenReplayCache {
+    pub fn check(&self, claims: &LogoutTokenClaims, clock_skew: u64) -> Result<(), Error> {
+        let now = Utc::now().timestamp();
+        let mut seen = self.seen.write().unwrap();
+        seen.retain(|_, expires_at| *expires_at > now);
+
+        let key = (claims.iss.clone(), claims.jti.clone());
+        if seen.contains_key(&key) {
+            return Err(Error::msg("Logout token has already been used"));
+        }
+        seen.insert(key, claims.exp.saturating_add(clock_skew as i64));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+
+    use super::*;
+
+    const SECRET: &[u8] = b"secret";
+
+    fn validate(claims: Value) -> Result<LogoutTokenClaims, Error> {
+        let logout_token = encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(SECRET),
+        )
+        .unwrap();
+
+        validate_logout_token(
+            &logout_token,
+            &DecodingKey::from_secret(SECRET),
+            Algorithm::HS256,
+            "https://issuer.example.com",
+            "client",
+            0,
+        )
+    }
+
+    fn claims() -> Value {
+        json!({
+            "iss": "https://issuer.example.com",
+            "aud": "client",
+            "iat": Utc::now().timestamp(),
+            "exp": Utc::now().timestamp() + 120,
+            "jti": "jti",
+            "sid": "sid",
+            "events": { BACKCHANNEL_LOGOUT_EVENT: {} },
+        })
+    }
+
+    #[test]
+    fn test_validate_logout_token() {
+        let claims = validate(claims()).unwrap();
+
+        assert_eq!(claims.sid.as_deref(), Some("sid"));
+    }
+
+    #[test]
+    fn test_validate_logout_token_rejects_invalid_claims() {
+        let mut without_event = claims();
+        without_event["events"] = json!({});
+        let mut without_sid = claims();
+        without_sid.as_object_mut().unwrap().remove("sid");
+        let mut with_nonce = claims();
+        with_nonce["nonce"] = json!("nonce");
+        let mut without_exp = claims();
+        without_exp.as_object_mut().unwrap().remove("exp");
+
+        for (claims, message) in [
+            (
+                without_event,
+                "Logout token is missing the back-channel logout event",
+            ),
+            (without_sid, "Logout token must contain sid or sub"),
+            (with_nonce, "Logout token must not contain a nonce"),
+        ] {
+            assert_eq!(validate(claims).unwrap_err().to_string(), message);
+        }
+        assert!(validate(without_exp).is_err());
+    }
+
+    #[test]
+    fn test_logout_token_replay_cache() {
+        let cache = LogoutTokenReplayCache::default();
+        let claims = validate(claims()).unwrap();
+
+        assert!(cache.check(&claims, 0).is_ok());
+        assert_eq!(
+            cache.check(&claims, 0).unwrap_err().to_string(),
+            "Logout token has already been used"
+        );
+
+        let mut other = claims.clone();
+        other.jti = "other".to_string();
+        assert!(cache.check(&other, 0).is_ok());
+    }
+}
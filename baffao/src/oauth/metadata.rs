@@ -0,0 +1,335 @@
+use std::str::FromStr;
+
+use anyhow::{Context, Error};
+use jsonwebtoken::Algorithm;
+use reqwest::Url;
+use serde::Deserialize;
+
+use super::OAuthConfig;
+
+const OPENID_CONFIGURATION_PATH: &str = ".well-known/openid-configuration";
+const OAUTH_AUTHORIZATION_SERVER_PATH: &str = ".well-known/oauth-authorization-server";
+// Default ID token signing algorithm of OpenID Connect
+const DEFAULT_SIGNING_ALGORITHM: Algorithm = Algorithm::RS256;
+
+/**
+ * ProviderMetadata
+ *
+ * Endpoints of the authorization server, discovered from the issuer and/or configured.
+ * OpenID Connect Discovery: https://openid.net/specs/openid-connect-discovery-1_0.html
+ * Authorization Server Metadata: https://datatracker.ietf.org/doc/html/rfc8414
+*/
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct ProviderMetadata {
+    pub issuer: Option<String>,
+    pub authorization_endpoint: Option<String>,
+    pub token_endpoint: Option<String>,
+    pub userinfo_endpoint: Option<String>,
+    pub revocation_endpoint: Option<String>,
+    pub introspection_endpoint: Option<String>,
+    pub end_session_endpoint: Option<String>,
+    pub jwks_uri: Option<String>,
+    pub pushed_authorization_request_endpoint: Option<String>,
+    pub require_pushed_authorization_requests: Option<bool>,
+    pub mtls_endpoint_aliases: Option<MtlsEndpointAliases>,
+    pub authorization_response_iss_parameter_supported: Option<bool>,
+    pub id_token_signing_alg_values_supported: Option<Vec<String>>,
+    pub authorization_signing_alg_values_supported: Option<Vec<String>>,
+}
+
+/**
+ * MtlsEndpointAliases
+ *
+ * Endpoints to use instead of the default ones when the client presents a certificate.
+ * Mutual-TLS Client Authentication: https://datatracker.ietf.org/doc/html/rfc8705#section-5
+*/
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct MtlsEndpointAliases {
+    pub token_endpoint: Option<String>,
+    pub userinfo_endpoint: Option<String>,
+    pub revocation_endpoint: Option<String>,
+    pub introspection_endpoint: Option<String>,
+    pub pushed_authorization_request_endpoint: Option<String>,
+}
+
+impl ProviderMetadata {
+    pub async fn discover(http_client: &reqwest::Client, issuer: &str) -> Result<Self, Error> {
+        let mut last_error = Error::msg("No discovery document found");
+        for url in well_known_urls(issuer)? {
+            let metadata = match fetch(http_client, url).await {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    last_error = e;
+                    continue;
+                }
+            };
+
+            if metadata.issuer.as_deref() != Some(issuer) {
+                return Err(Error::msg(format!(
+                    "Issuer mismatch: expected {}, got {}",
+                    issuer,
+                    metadata.issuer.unwrap_or_default()
+                )));
+            }
+
+            return Ok(metadata);
+        }
+
+        Err(last_error)
+    }
+
+    /**
+     * Algorithms the provider signatures are accepted with, the configured ones or else the
+     * asymmetric ones the provider advertises. The HMAC algorithms use the client secret as key
+     * and are only accepted when configured explicitly.
+     */
+    pub fn signing_algorithms(&self, configured: Option<&[Algorithm]>) -> Vec<Algorithm> {
+        allowed_algorithms(
+            self.id_token_signing_alg_values_supported.as_deref(),
+            configured,
+        )
+    }
+
+    /**
+     * Algorithms the JWT authorization responses are accepted with, as for the ID tokens but
+     * from the algorithms the provider advertises for them.
+     * JARM: https://openid.net/specs/oauth-v2-jarm.html#name-authorization-server-metada
+     */
+    pub fn authorization_signing_algorithms(
+        &self,
+        configured: Option<&[Algorithm]>,
+    ) -> Vec<Algorithm> {
+        allowed_algorithms(
+            self.authorization_signing_alg_values_supported.as_deref(),
+            configured,
+        )
+    }
+
+    // Endpoints set in the configuration take precedence over the discovered ones
+    pub fn with_overrides(self, config: &OAuthConfig) -> Self {
+        let metadata = match (&config.tls, self.mtls_endpoint_aliases.clone()) {
+            (Some(_), Some(aliases)) => self.with_mtls_endpoint_aliases(aliases),
+            _ => self,
+        };
+
+        metadata.with_configured_endpoints(config)
+    }
+
+    fn with_mtls_endpoint_aliases(self, aliases: MtlsEndpointAliases) -> Self {
+        Self {
+            token_endpoint: aliases.token_endpoint.or(self.token_endpoint),
+            userinfo_endpoint: aliases.userinfo_endpoint.or(self.userinfo_endpoint),
+            revocation_endpoint: aliases.revocation_endpoint.or(self.revocation_endpoint),
+            introspection_endpoint: aliases
+                .introspection_endpoint
+                .or(self.introspection_endpoint),
+            pushed_authorization_request_endpoint: aliases
+                .pushed_authorization_request_endpoint
+                .or(self.pushed_authorization_request_endpoint),
+            ..self
+        }
+    }
+
+    fn with_configured_endpoints(self, config: &OAuthConfig) -> Self {
+        Self {
+            issuer: config.issuer.clone().or(self.issuer),
+            authorization_endpoint: config
+                .authorization_endpoint
+                .clone()
+                .or(self.authorization_endpoint),
+            token_endpoint: config.token_endpoint.clone().or(self.token_endpoint),
+            userinfo_endpoint: config.userinfo_endpoint.clone().or(self.userinfo_endpoint),
+            revocation_endpoint: config
+                .revocation_endpoint
+                .clone()
+                .or(self.revocation_endpoint),
+            introspection_endpoint: config
+                .introspection_endpoint
+                .clone()
+                .or(self.introspection_endpoint),
+            end_session_endpoint: config
+                .end_session_endpoint
+                .clone()
+                .or(self.end_session_endpoint),
+            jwks_uri: config.jwks_uri.clone().or(self.jwks_uri),
+            pushed_authorization_request_endpoint: config
+                .pushed_authorization_request_endpoint
+                .clone()
+                .or(self.pushed_authorization_request_endpoint),
+            require_pushed_authorization_requests: self.require_pushed_authorization_requests,
+            mtls_endpoint_aliases: self.mtls_endpoint_aliases,
+            authorization_response_iss_parameter_supported: self
+                .authorization_response_iss_parameter_supported,
+            id_token_signing_alg_values_supported: self.id_token_signing_alg_values_supported,
+            authorization_signing_alg_values_supported: self
+                .authorization_signing_alg_values_supported,
+        }
+    }
+}
+
+fn allowed_algorithms(
+    advertised: Option<&[String]>,
+    configured: Option<&[Algorithm]>,
+) -> Vec<Algorithm> {
+    if let Some(configured) = configured {
+        return configured.to_vec();
+    }
+
+    let advertised = advertised
+        .iter()
+        .copied()
+        .flatten()
+        .filter_map(|alg| Algorithm::from_str(alg).ok())
+        .filter(|alg| !is_hmac(*alg))
+        .collect::<Vec<_>>();
+    if advertised.is_empty() {
+        return vec![DEFAULT_SIGNING_ALGORITHM];
+    }
+
+    advertised
+}
+
+pub fn is_hmac(alg: Algorithm) -> bool {
+    matches!(alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512)
+}
+
+async fn fetch(http_client: &reqwest::Client, url: Url) -> Result<ProviderMetadata, Error> {
+    let response = http_client
+        .get(url.clone())
+        .header(http::header::ACCEPT, "application/json")
+        .send()
+        .await?
+        .error_for_status()?;
+
+    response
+        .json::<ProviderMetadata>()
+        .await
+        .with_context(|| format!("Failed to parse metadata from {}", url))
+}
+
+/**
+ * OpenID Connect appends the well-known path to the issuer whereas RFC 8414 inserts it
+ * between the host and the issuer path.
+*/
+fn well_known_urls(issuer: &str) -> Result<Vec<Url>, Error> {
+    let openid_configuration = Url::parse(&format!(
+        "{}/{}",
+        issuer.trim_end_matches('/'),
+        OPENID_CONFIGURATION_PATH
+    ))
+    .context("Failed to parse issuer")?;
+
+    let mut oauth_authorization_server = Url::parse(issuer).context("Failed to parse issuer")?;
+    let issuer_path = oauth_authorization_server
+        .path()
+        .trim_end_matches('/')
+        .to_string();
+    oauth_authorization_server.set_path(&format!(
+        "/{}{}",
+        OAUTH_AUTHORIZATION_SERVER_PATH, issuer_path
+    ));
+
+    Ok(vec![openid_configuration, oauth_authorization_server])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_well_known_urls() {
+        let urls = well_known_urls("https://example.com/").unwrap();
+        assert_eq!(
+            urls[0].as_str(),
+            "https://example.com/.well-known/openid-configuration"
+        );
+        assert_eq!(
+            urls[1].as_str(),
+            "https://example.com/.well-known/oauth-authorization-server"
+        );
+
+        let urls = well_known_urls("https://example.com/tenant").unwrap();
+        assert_eq!(
+            urls[0].as_str(),
+            "https://example.com/tenant/.well-known/openid-configuration"
+        );
+        assert_eq!(
+            urls[1].as_str(),
+            "https://example.com/.well-known/oauth-authorization-server/tenant"
+        );
+    }
+
+    #[test]
+    fn test_signing_algorithms() {
+        let metadata = ProviderMetadata {
+            id_token_signing_alg_values_supported: Some(vec![
+                "RS256".to_string(),
+                "HS256".to_string(),
+                "none".to_string(),
+                "ES256".to_string(),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            metadata.signing_algorithms(None),
+            vec![Algorithm::RS256, Algorithm::ES256]
+        );
+        assert_eq!(
+            metadata.signing_algorithms(Some(&[Algorithm::HS256])),
+            vec![Algorithm::HS256]
+        );
+        assert_eq!(
+            ProviderMetadata::default().signing_algorithms(None),
+            vec![Algorithm::RS256]
+        );
+    }
+
+    #[test]
+    fn test_authorization_signing_algorithms() {
+        let metadata = ProviderMetadata {
+            id_token_signing_alg_values_supported: Some(vec!["RS256".to_string()]),
+            authorization_signing_alg_values_supported: Some(vec!["PS256".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            metadata.authorization_signing_algorithms(None),
+            vec![Algorithm::PS256]
+        );
+        assert_eq!(
+            ProviderMetadata::default().authorization_signing_algorithms(None),
+            vec![Algorithm::RS256]
+        );
+    }
+
+    #[test]
+    fn test_with_overrides_mtls_endpoint_aliases() {
+        let metadata = ProviderMetadata {
+            token_endpoint: Some("https://example.com/token".to_string()),
+            userinfo_endpoint: Some("https://example.com/userinfo".to_string()),
+            mtls_endpoint_aliases: Some(MtlsEndpointAliases {
+                token_endpoint: Some("https://mtls.example.com/token".to_string()),
+                userinfo_endpoint: Some("https://mtls.example.com/userinfo".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let config: OAuthConfig = serde_json::from_value(serde_json::json!({
+            "client_id": "client",
+            "authorization_redirect_uri": "https://app.example.com/oauth/callback",
+            "userinfo_endpoint": "https://example.com/configured/userinfo",
+            "tls": { "certificate": "client.pem", "key": "client.key" },
+        }))
+        .unwrap();
+
+        let metadata = metadata.with_overrides(&config);
+
+        assert_eq!(
+            metadata.token_endpoint.as_deref(),
+            Some("https://mtls.example.com/token")
+        );
+        assert_eq!(
+            metadata.userinfo_endpoint.as_deref(),
+            Some("https://example.com/configured/userinfo")
+        );
+    }
+}
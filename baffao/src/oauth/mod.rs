@@ -1,29 +1,123 @@
+pub use authorization_request::AuthorizationRequest;
 pub use client::OAuthClient;
+pub use client_auth::ClientAuthMethod;
+pub use dpop::{DpopKey, DPOP_HEADER, DPOP_NONCE_HEADER, DPOP_TOKEN_TYPE};
 pub use http::OAuthHttpHandler;
+pub use id_token::{Audience, IdTokenClaims};
+pub use introspection::IntrospectedToken;
+pub use logout_token::LogoutTokenClaims;
+pub use metadata::ProviderMetadata;
+pub use par::PushedAuthorizationRequests;
+pub use providers::OAuthProviders;
+pub use response_mode::{AuthorizationResponseClaims, ResponseMode};
+pub use signing_key::SigningKeyConfig;
+pub use state::AuthorizationState;
+pub use step_up::AuthenticationRequirement;
+pub use tls::TlsClientConfig;
+pub use token_exchange::TokenExchangeRequest;
+pub use userinfo::merge_userinfo;
 
+mod authorization_request;
 mod client;
+mod client_auth;
+mod client_credentials;
+mod dpop;
 mod http;
+mod http_client;
+mod id_token;
+mod introspection;
+mod jwks;
+mod logout_token;
+mod metadata;
+mod par;
+mod providers;
+mod request_object;
+mod response_mode;
+mod signing_key;
+mod state;
+mod step_up;
+mod tls;
+mod token_exchange;
+mod userinfo;
 
-use oauth2::{basic::BasicTokenType, EmptyExtraTokenFields, StandardTokenResponse};
+use std::time::Duration;
 
-use serde::Deserialize;
+use jsonwebtoken::Algorithm;
+use oauth2::{basic::BasicTokenType, ExtraTokenFields, StandardTokenResponse};
+
+use serde::{Deserialize, Serialize};
 
 /**
  * OAuthConfig
  *
  * This struct is used to store the OAuth configuration.
+ * When an issuer is set, the endpoints are discovered from the provider metadata and
+ * re-fetched every `metadata_refresh_interval` seconds. Endpoints set explicitly take precedence.
+ * Userinfo is fetched after the code exchange unless `fetch_userinfo` is false, and after
+ * each token refresh when `refresh_userinfo` is true.
+ * When `introspection` is true, access tokens are introspected before being used, which
+ * requires an introspection endpoint.
+ * The client authenticates with `client_auth_method`, the client secret is not needed
+ * with `private_key_jwt` which signs the client assertions with `signing_key`.
+ * The `tls` client certificate is presented to the provider, which is required by the mutual
+ * TLS methods and binds the access tokens to the certificate. The mTLS endpoint aliases of the
+ * provider are then used.
+ * The `resources` are requested on authorization, a token is then obtained for each of them
+ * when it is first used.
+ * The ID tokens, logout tokens and signed responses of the provider are accepted when signed
+ * with one of the `signing_algorithms`, by default the asymmetric ones the provider advertises.
+ * The HMAC algorithms, keyed with the client secret, must be listed explicitly.
+ * When `dpop` is true, the access tokens are bound to a DPoP key generated for each session.
+ * When `request_object` is true, the authorization request is sent as a request object signed
+ * with `signing_key`.
+ * The `response_mode` selects how the authorization response is returned to the callback, the
+ * form post modes keep the login cookies cross-site and the JWT modes validate the signed
+ * response against the provider keys.
  * Authorization Server Metadata: https://datatracker.ietf.org/doc/html/rfc8414
 */
 #[derive(Deserialize, Clone)]
 #[allow(unused)]
 pub struct OAuthConfig {
     pub client_id: String,
-    pub client_secret: String,
+    pub client_secret: Option<String>,
+    pub client_auth_method: Option<ClientAuthMethod>,
     pub authorization_redirect_uri: String,
-    pub authorization_endpoint: String,
-    pub token_endpoint: String,
+    pub issuer: Option<String>,
+    pub authorization_endpoint: Option<String>,
+    pub token_endpoint: Option<String>,
+    pub userinfo_endpoint: Option<String>,
+    pub revocation_endpoint: Option<String>,
+    pub introspection_endpoint: Option<String>,
+    pub end_session_endpoint: Option<String>,
+    pub jwks_uri: Option<String>,
+    pub pushed_authorization_request_endpoint: Option<String>,
+    pub pushed_authorization_requests: Option<PushedAuthorizationRequests>,
+    pub request_object: Option<bool>,
+    pub response_mode: Option<ResponseMode>,
+    pub signing_key: Option<SigningKeyConfig>,
+    pub tls: Option<TlsClientConfig>,
+    pub signing_algorithms: Option<Vec<Algorithm>>,
+    pub dpop: Option<bool>,
+    pub metadata_refresh_interval: Option<u64>,
+    pub clock_skew: Option<u64>,
+    pub fetch_userinfo: Option<bool>,
+    pub refresh_userinfo: Option<bool>,
+    pub introspection: Option<bool>,
+    pub introspection_cache_ttl: Option<u64>,
     pub redirect_uri: Option<String>,
+    pub post_logout_redirect_uri: Option<String>,
     pub default_scopes: Option<Vec<String>>,
+    pub resources: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IdTokenFields {
+    pub id_token: Option<String>,
 }
 
-pub type AccessToken = StandardTokenResponse<EmptyExtraTokenFields, BasicTokenType>;
+impl ExtraTokenFields for IdTokenFields {}
+
+pub type AccessToken = StandardTokenResponse<IdTokenFields, BasicTokenType>;
+
+// Lifetime assumed for the tokens issued without `expires_in`, they are not cached longer
+pub const DEFAULT_TOKEN_TTL: Duration = Duration::from_secs(300);
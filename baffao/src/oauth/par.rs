@@ -0,0 +1,85 @@
+use anyhow::{Context, Error};
+use reqwest::Url;
+use serde::Deserialize;
+
+/**
+ * PushedAuthorizationRequests
+ *
+ * `enabled` pushes the authorization request when the provider has a PAR endpoint and falls
+ * back to the front-channel request otherwise, `required` fails when it cannot be pushed.
+ * Pushed Authorization Requests: https://datatracker.ietf.org/doc/html/rfc9126
+*/
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PushedAuthorizationRequests {
+    #[default]
+    Disabled,
+    Enabled,
+    Required,
+}
+
+#[derive(Deserialize)]
+pub struct PushedAuthorizationResponse {
+    pub request_uri: String,
+}
+
+/**
+ * Send the authorization request, the request builder already holds the parameters and the
+ * client authentication.
+*/
+pub async fn push_authorization_request(
+    request: reqwest::RequestBuilder,
+) -> Result<PushedAuthorizationResponse, Error> {
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        return Err(Error::msg(format!(
+            "Failed to push authorization request: {}",
+            response.text().await.unwrap_or_default()
+        )));
+    }
+
+    response
+        .json::<PushedAuthorizationResponse>()
+        .await
+        .context("Invalid pushed authorization response")
+}
+
+/**
+ * Only the client id and the request uri are sent through the browser.
+*/
+pub fn authorization_url_with_request_uri(
+    authorization_url: &Url,
+    client_id: &str,
+    request_uri: &str,
+) -> Url {
+    let mut url = authorization_url.clone();
+    url.set_query(None);
+    url.query_pairs_mut()
+        .append_pair("client_id", client_id)
+        .append_pair("request_uri", request_uri);
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authorization_url_with_request_uri() {
+        let authorization_url = Url::parse(
+            "https://example.com/authorize?response_type=code&client_id=client&state=state",
+        )
+        .unwrap();
+
+        let url = authorization_url_with_request_uri(
+            &authorization_url,
+            "client",
+            "urn:ietf:params:oauth:request_uri:abc",
+        );
+
+        assert_eq!(
+            url.as_str(),
+            "https://example.com/authorize?client_id=client&request_uri=urn%3Aietf%3Aparams%3Aoauth%3Arequest_uri%3Aabc"
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Context, Error};
+use axum_extra::extract::CookieJar;
+
+use super::{OAuthConfig, OAuthHttpHandler};
+use crate::{
+    session::{MemorySessionStore, SessionStore},
+    settings::{CookiesConfig, SessionConfig},
+};
+
+/**
+ * OAuthProviders
+ *
+ * The default provider and the named ones, sharing the cookies and the session store.
+ * A session remembers the provider it was created with so that the following token refreshes,
+ * revocations and logouts go to the same provider, and the login state remembers it as well so
+ * that the callback completes the login with the provider it was started with.
+*/
+#[derive(Clone)]
+pub struct OAuthProviders {
+    default_provider: OAuthHttpHandler,
+    providers: Arc<HashMap<String, OAuthHttpHandler>>,
+    session_store: Arc<dyn SessionStore>,
+}
+
+impl OAuthProviders {
+    pub async fn new(
+        oauth_config: OAuthConfig,
+        providers_config: HashMap<String, OAuthConfig>,
+        cookies_config: CookiesConfig,
+        session_config: SessionConfig,
+    ) -> Result<Self, Error> {
+        let session_store: Arc<dyn SessionStore> = Arc::new(MemorySessionStore::default());
+        let default_provider =
+            OAuthHttpHandler::new(oauth_config, cookies_config.clone(), session_config.clone())
+                .await?
+                .with_session_store(session_store.clone());
+
+        let mut providers = HashMap::new();
+        for (name, config) in providers_config {
+            let handler =
+                OAuthHttpHandler::new(config, cookies_config.clone(), session_config.clone())
+                    .await
+                    .with_context(|| format!("Failed to configure the provider {}", name))?
+                    .with_session_store(session_store.clone())
+                    .with_provider(Some(name.clone()));
+            providers.insert(name, handler);
+        }
+
+        Ok(Self {
+            default_provider,
+            providers: Arc::new(providers),
+            session_store,
+        })
+    }
+
+    /**
+     * Store shared by the providers, expired sessions must be removed from it periodically.
+     */
+    pub fn session_store(&self) -> Arc<dyn SessionStore> {
+        self.session_store.clone()
+    }
+
+    pub fn default_provider(&self) -> OAuthHttpHandler {
+        self.default_provider.clone()
+    }
+
+    pub fn get(&self, name: Option<&str>) -> Option<OAuthHttpHandler> {
+        match name {
+            Some(name) => self.providers.get(name).cloned(),
+            None => Some(self.default_provider()),
+        }
+    }
+
+    /**
+     * Provider the login in progress was started with, the default one when there is no login
+     * in progress. None when the provider is no longer configured.
+     */
+    pub fn for_callback(&self, jar: &CookieJar) -> Option<OAuthHttpHandler> {
+        match self.default_provider.authorization_state(jar) {
+            Some(state) => self.get(state.provider()),
+            None => Some(self.default_provider()),
+        }
+    }
+
+    /**
+     * Provider of the current session, the default one when there is no session. An expired
+     * session keeps its provider, which its refresh token belongs to.
+     */
+    pub async fn for_session(&self, jar: &CookieJar) -> OAuthHttpHandler {
+        self.default_provider
+            .find_session(jar)
+            .await
+            .and_then(|session| self.get(session.provider()))
+            .unwrap_or_else(|| self.default_provider())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum_extra::extract::cookie::SameSite;
+    use chrono::{Duration, Utc};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        cookies::new_cookie,
+        oauth::AuthorizationState,
+        session::{update_session, Session},
+        settings::CookieConfig,
+    };
+
+    fn oauth_config() -> OAuthConfig {
+        serde_json::from_value(json!({
+            "client_id": "client",
+            "client_secret": "secret",
+            "authorization_redirect_uri": "http://localhost/oauth/callback",
+            "authorization_endpoint": "http://localhost/authorize",
+            "token_endpoint": "http://localhost/token",
+        }))
+        .unwrap()
+    }
+
+    fn cookie(name: &str) -> CookieConfig {
+        CookieConfig {
+            name: name.to_string(),
+            domain: "localhost".to_string(),
+            secure: false,
+            http_only: true,
+            same_site: SameSite::Lax,
+        }
+    }
+
+    fn cookies_config() -> CookiesConfig {
+        CookiesConfig {
+            oauth_csrf: cookie("csrf"),
+            oauth_pkce: cookie("pkce"),
+            oauth_nonce: cookie("nonce"),
+            access_token: cookie("access_token"),
+            refresh_token: cookie("refresh_token"),
+            id_token: cookie("id_token"),
+            session: cookie("session"),
+        }
+    }
+
+    async fn providers() -> OAuthProviders {
+        OAuthProviders::new(
+            oauth_config(),
+            HashMap::from([("other".to_string(), oauth_config())]),
+            cookies_config(),
+            SessionConfig::default(),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_get() {
+        let providers = providers().await;
+
+        assert_eq!(providers.get(None).unwrap().provider(), None);
+        assert_eq!(
+            providers.get(Some("other")).unwrap().provider(),
+            Some("other")
+        );
+        assert!(providers.get(Some("unknown")).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_for_session() {
+        let providers = providers().await;
+        assert_eq!(
+            providers.for_session(&CookieJar::new()).await.provider(),
+            None
+        );
+
+        let session = Session::new(None, None, None).with_provider(Some("other".to_string()));
+        providers.session_store().insert(session.clone()).await;
+        let jar = update_session(CookieJar::new(), cookie("session"), Some(session));
+        assert_eq!(providers.for_session(&jar).await.provider(), Some("other"));
+
+        let session = Session::new(None, None, Some(Utc::now() - Duration::hours(1)))
+            .with_provider(Some("other".to_string()));
+        providers.session_store().insert(session.clone()).await;
+        let jar = update_session(CookieJar::new(), cookie("session"), Some(session));
+        assert_eq!(providers.for_session(&jar).await.provider(), Some("other"));
+    }
+
+    #[tokio::test]
+    async fn test_for_callback() {
+        let providers = providers().await;
+        let jar = |provider: Option<&str>| {
+            let state = AuthorizationState::new("csrf".to_string(), None)
+                .with_provider(provider.map(String::from));
+            CookieJar::new().add(new_cookie(cookie("csrf"), state.encode()))
+        };
+
+        assert_eq!(
+            providers
+                .for_callback(&CookieJar::new())
+                .unwrap()
+                .provider(),
+            None
+        );
+        assert_eq!(providers.for_callback(&jar(None)).unwrap().provider(), None);
+        assert_eq!(
+            providers
+                .for_callback(&jar(Some("other")))
+                .unwrap()
+                .provider(),
+            Some("other")
+        );
+        assert!(providers.for_callback(&jar(Some("unknown"))).is_none());
+    }
+}
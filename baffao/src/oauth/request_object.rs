@@ -0,0 +1,110 @@
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use serde_json::{Map, Value};
+
+pub const REQUEST_OBJECT_TYPE: &str = "oauth-authz-req+jwt";
+const REQUEST_OBJECT_LIFETIME: i64 = 300;
+
+/**
+ * Claims of the request object, all the authorization request parameters plus the client as
+ * issuer and the provider as audience.
+ * JWT-Secured Authorization Request: https://datatracker.ietf.org/doc/html/rfc9101
+*/
+pub fn request_object_claims(
+    params: &[(String, String)],
+    client_id: &str,
+    audience: &str,
+    jti: &str,
+    now: DateTime<Utc>,
+) -> Map<String, Value> {
+    let mut claims = Map::new();
+    for (name, value) in params {
+        // Repeated parameters, such as `resource`, become arrays
+        match claims.get_mut(name) {
+            Some(Value::Array(values)) => values.push(value.as_str().into()),
+            Some(previous) => {
+                *previous = Value::Array(vec![previous.clone(), value.as_str().into()])
+            }
+            None => {
+                claims.insert(name.clone(), value.as_str().into());
+            }
+        }
+    }
+    claims.insert("iss".to_string(), client_id.into());
+    claims.insert("client_id".to_string(), client_id.into());
+    claims.insert("aud".to_string(), audience.into());
+    claims.insert("jti".to_string(), jti.into());
+    claims.insert("iat".to_string(), now.timestamp().into());
+    claims.insert("nbf".to_string(), now.timestamp().into());
+    claims.insert(
+        "exp".to_string(),
+        (now.timestamp() + REQUEST_OBJECT_LIFETIME).into(),
+    );
+    claims
+}
+
+/**
+ * The parameters are only sent in the request object, except `response_type` and `scope`
+ * which OpenID Connect also requires in the query.
+*/
+pub fn authorization_url_with_request(
+    authorization_url: &Url,
+    client_id: &str,
+    request: &str,
+) -> Url {
+    let mut url = authorization_url.clone();
+    let params = authorization_url
+        .query_pairs()
+        .into_owned()
+        .filter(|(name, _)| name == "response_type" || name == "scope")
+        .collect::<Vec<_>>();
+    url.set_query(None);
+    url.query_pairs_mut()
+        .append_pair("client_id", client_id)
+        .extend_pairs(params)
+        .append_pair("request", request);
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_object_claims() {
+        let now = Utc::now();
+        let params = vec![
+            ("response_type".to_string(), "code".to_string()),
+            ("state".to_string(), "state".to_string()),
+            ("resource".to_string(), "https://a.example.com/".to_string()),
+            ("resource".to_string(), "https://b.example.com/".to_string()),
+        ];
+
+        let claims = request_object_claims(&params, "client", "https://example.com/", "jti", now);
+
+        assert_eq!(claims["response_type"], "code");
+        assert_eq!(claims["state"], "state");
+        assert_eq!(
+            claims["resource"],
+            serde_json::json!(["https://a.example.com/", "https://b.example.com/"])
+        );
+        assert_eq!(claims["iss"], "client");
+        assert_eq!(claims["aud"], "https://example.com/");
+        assert_eq!(claims["exp"], now.timestamp() + REQUEST_OBJECT_LIFETIME);
+    }
+
+    #[test]
+    fn test_authorization_url_with_request() {
+        let authorization_url = Url::parse(
+            "https://example.com/authorize?response_type=code&client_id=client&state=state&scope=openid",
+        )
+        .unwrap();
+
+        let url = authorization_url_with_request(&authorization_url, "client", "jwt");
+
+        assert_eq!(
+            url.as_str(),
+            "https://example.com/authorize?client_id=client&response_type=code&scope=openid&request=jwt"
+        );
+    }
+}
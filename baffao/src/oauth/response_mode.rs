@@ -0,0 +1,147 @@
+use anyhow::Error;
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+
+use super::Audience;
+
+/**
+ * ResponseMode
+ *
+ * How the provider returns the authorization response: in the query of a redirect, in a form
+ * posted to the redirect uri or, with JARM, as a signed `response` JWT in either of them.
+ * Form Post Response Mode: https://openid.net/specs/oauth-v2-form-post-response-mode-1_0.html
+ * JARM: https://openid.net/specs/oauth-v2-jarm.html
+*/
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ResponseMode {
+    #[default]
+    #[serde(rename = "query")]
+    Query,
+    #[serde(rename = "form_post")]
+    FormPost,
+    #[serde(rename = "jwt")]
+    Jwt,
+    #[serde(rename = "form_post.jwt")]
+    FormPostJwt,
+}
+
+impl ResponseMode {
+    // The query response mode is the default of the code flow and is not sent
+    pub fn param(&self) -> Option<&'static str> {
+        match self {
+            ResponseMode::Query => None,
+            ResponseMode::FormPost => Some("form_post"),
+            ResponseMode::Jwt => Some("jwt"),
+            ResponseMode::FormPostJwt => Some("form_post.jwt"),
+        }
+    }
+
+    pub fn is_form_post(&self) -> bool {
+        matches!(self, ResponseMode::FormPost | ResponseMode::FormPostJwt)
+    }
+
+    pub fn is_jwt(&self) -> bool {
+        matches!(self, ResponseMode::Jwt | ResponseMode::FormPostJwt)
+    }
+}
+
+/**
+ * AuthorizationResponseClaims
+ *
+ * JWT Secured Authorization Response: https://openid.net/specs/oauth-v2-jarm.html#section-2.1
+*/
+#[derive(Deserialize, Clone, Debug)]
+pub struct AuthorizationResponseClaims {
+    pub iss: String,
+    pub aud: Audience,
+    pub exp: i64,
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+pub fn validate_authorization_response(
+    response: &str,
+    key: &DecodingKey,
+    alg: Algorithm,
+    issuer: &str,
+    client_id: &str,
+    clock_skew: u64,
+) -> Result<AuthorizationResponseClaims, Error> {
+    let mut validation = Validation::new(alg);
+    validation.leeway = clock_skew;
+    validation.set_issuer(&[issuer]);
+    validation.set_audience(&[client_id]);
+    validation.set_required_spec_claims(&["iss", "aud", "exp"]);
+
+    decode::<AuthorizationResponseClaims>(response, key, &validation)
+        .map(|data| data.claims)
+        .map_err(|e| Error::msg(format!("Invalid authorization response: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    const SECRET: &[u8] = b"secret";
+
+    fn validate(claims: Value) -> Result<AuthorizationResponseClaims, Error> {
+        let response = encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(SECRET),
+        )
+        .unwrap();
+        validate_authorization_response(
+            &response,
+            &DecodingKey::from_secret(SECRET),
+            Algorithm::HS256,
+            "https://example.com/",
+            "client",
+            0,
+        )
+    }
+
+    #[test]
+    fn test_validate_authorization_response() {
+        let exp = Utc::now().timestamp() + 600;
+        let claims = validate(json!({
+            "iss": "https://example.com/",
+            "aud": "client",
+            "exp": exp,
+            "code": "code",
+            "state": "state",
+        }))
+        .unwrap();
+        assert_eq!(claims.code.as_deref(), Some("code"));
+        assert_eq!(claims.state.as_deref(), Some("state"));
+
+        assert!(validate(json!({
+            "iss": "https://example.com/",
+            "aud": "another-client",
+            "exp": exp,
+            "code": "code",
+        }))
+        .is_err());
+        assert!(validate(json!({
+            "iss": "https://example.com/",
+            "aud": "client",
+            "exp": Utc::now().timestamp() - 600,
+            "code": "code",
+        }))
+        .is_err());
+    }
+
+    #[test]
+    fn test_response_mode() {
+        let mode: ResponseMode = serde_json::from_value(json!("form_post.jwt")).unwrap();
+        assert_eq!(mode, ResponseMode::FormPostJwt);
+        assert!(mode.is_form_post() && mode.is_jwt());
+        assert_eq!(ResponseMode::default().param(), None);
+    }
+}
@@ -0,0 +1,72 @@
+use std::fs;
+
+use anyhow::{Context, Error};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+
+/**
+ * SigningKeyConfig
+ *
+ * Private key of the client used to sign the JWTs it sends to the provider, loaded from a
+ * PEM file. RSA (RS*, PS*), EC (ES256, ES384) and Ed25519 (EdDSA) keys are supported.
+*/
+#[derive(Deserialize, Clone, Debug)]
+pub struct SigningKeyConfig {
+    pub path: String,
+    pub algorithm: Algorithm,
+    pub kid: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct SigningKey {
+    key: EncodingKey,
+    algorithm: Algorithm,
+    kid: Option<String>,
+}
+
+impl SigningKey {
+    pub fn load(config: &SigningKeyConfig) -> Result<Self, Error> {
+        let pem = fs::read(&config.path)
+            .with_context(|| format!("Failed to read signing key {}", config.path))?;
+        let key = match config.algorithm {
+            Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512 => EncodingKey::from_rsa_pem(&pem),
+            Algorithm::ES256 | Algorithm::ES384 => EncodingKey::from_ec_pem(&pem),
+            Algorithm::EdDSA => EncodingKey::from_ed_pem(&pem),
+            algorithm => {
+                return Err(Error::msg(format!(
+                    "Unsupported signing algorithm {:?}",
+                    algorithm
+                )))
+            }
+        }
+        .context("Invalid signing key")?;
+
+        Ok(Self {
+            key,
+            algorithm: config.algorithm,
+            kid: config.kid.clone(),
+        })
+    }
+
+    // Symmetric key used for the `client_secret_jwt` client authentication
+    pub fn from_secret(secret: &str) -> Self {
+        Self {
+            key: EncodingKey::from_secret(secret.as_bytes()),
+            algorithm: Algorithm::HS256,
+            kid: None,
+        }
+    }
+
+    pub fn sign<T: Serialize>(&self, typ: &str, claims: &T) -> Result<String, Error> {
+        let mut header = Header::new(self.algorithm);
+        header.typ = Some(typ.to_string());
+        header.kid = self.kid.clone();
+
+        encode(&header, claims, &self.key).context("Failed to sign JWT")
+    }
+}
@@ -0,0 +1,193 @@
+use anyhow::{Context, Error};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde::{Deserialize, Serialize};
+
+const DATA_SEPARATOR: char = '.';
+
+/**
+ * AuthorizationState
+ *
+ * CSRF state of a login together with what the callback needs to know about it, all kept in
+ * the CSRF cookie: the issuer the login was started with, to detect an authorization server
+ * mix-up, the provider handling the callback, the path to return the application to, whether
+ * the login is silent and the requested scopes, which are granted when the token response does
+ * not list them.
+ * Authorization Server Issuer Identification: https://datatracker.ietf.org/doc/html/rfc9207
+*/
+#[derive(Debug, Default, PartialEq)]
+pub struct AuthorizationState {
+    pub csrf_token: String,
+    pub data: AuthorizationStateData,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct AuthorizationStateData {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub return_to: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub silent: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<Vec<String>>,
+}
+
+impl AuthorizationState {
+    pub fn new(csrf_token: String, issuer: Option<String>) -> Self {
+        Self {
+            csrf_token,
+            data: AuthorizationStateData {
+                issuer,
+                ..Default::default()
+            },
+        }
+    }
+
+    // Only local paths are kept, the callback must not redirect to another site
+    pub fn with_return_to(mut self, return_to: Option<String>) -> Self {
+        self.data.return_to = return_to.filter(|return_to| is_local_path(return_to));
+        self
+    }
+
+    pub fn with_silent(mut self, silent: bool) -> Self {
+        self.data.silent = silent;
+        self
+    }
+
+    pub fn with_scope(mut self, scope: Option<Vec<String>>) -> Self {
+        self.data.scope = scope;
+        self
+    }
+
+    pub fn with_provider(mut self, provider: Option<String>) -> Self {
+        self.data.provider = provider;
+        self
+    }
+
+    pub fn issuer(&self) -> Option<&str> {
+        self.data.issuer.as_deref()
+    }
+
+    pub fn provider(&self) -> Option<&str> {
+        self.data.provider.as_deref()
+    }
+
+    pub fn return_to(&self) -> &str {
+        self.data.return_to.as_deref().unwrap_or("/")
+    }
+
+    pub fn is_silent(&self) -> bool {
+        self.data.silent
+    }
+
+    pub fn scope(&self) -> Option<&Vec<String>> {
+        self.data.scope.as_ref()
+    }
+
+    // The CSRF token is base64url encoded and never contains the separator
+    pub fn encode(&self) -> String {
+        if self.data == AuthorizationStateData::default() {
+            return self.csrf_token.clone();
+        }
+
+        let data = serde_json::to_string(&self.data).unwrap();
+        format!(
+            "{}{}{}",
+            self.csrf_token,
+            DATA_SEPARATOR,
+            URL_SAFE_NO_PAD.encode(data)
+        )
+    }
+
+    pub fn decode(encoded: &str) -> Result<Self, Error> {
+        let Some((csrf_token, data)) = encoded.split_once(DATA_SEPARATOR) else {
+            return Ok(Self::new(encoded.to_string(), None));
+        };
+        let data = URL_SAFE_NO_PAD
+            .decode(data)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .context("Invalid authorization state")?;
+
+        Ok(Self {
+            csrf_token: csrf_token.to_string(),
+            data,
+        })
+    }
+
+    /**
+     * Check the `iss` parameter of the authorization response, it is required when the
+     * provider advertises `authorization_response_iss_parameter_supported`.
+     */
+    pub fn validate_issuer(&self, iss: Option<&str>, required: bool) -> Result<(), Error> {
+        match (iss, self.issuer()) {
+            (None, _) if required => Err(Error::msg("Issuer not found")),
+            (None, _) => Ok(()),
+            (Some(iss), Some(issuer)) if iss == issuer => Ok(()),
+            (Some(_), _) => Err(Error::msg("Issuer mismatch")),
+        }
+    }
+}
+
+// Browsers strip tabs and newlines from urls, `/\t/host` would become `//host`
+fn is_local_path(path: &str) -> bool {
+    path.starts_with('/')
+        && !path.starts_with("//")
+        && !path.starts_with("/\\")
+        && !path.chars().any(char::is_control)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authorization_state_encoding() {
+        let state =
+            AuthorizationState::new("csrf".to_string(), Some("https://example.com/".to_string()))
+                .with_provider(Some("other".to_string()))
+                .with_return_to(Some("/orders?page=2".to_string()))
+                .with_silent(true)
+                .with_scope(Some(vec!["openid".to_string()]));
+        assert_eq!(AuthorizationState::decode(&state.encode()).unwrap(), state);
+
+        let state = AuthorizationState::new("csrf".to_string(), None);
+        assert_eq!(state.encode(), "csrf");
+        assert_eq!(AuthorizationState::decode("csrf").unwrap(), state);
+    }
+
+    #[test]
+    fn test_return_to() {
+        let state = |return_to: &str| {
+            AuthorizationState::new("csrf".to_string(), None)
+                .with_return_to(Some(return_to.to_string()))
+        };
+        assert_eq!(state("/orders").return_to(), "/orders");
+        assert_eq!(state("https://attacker.example.com/").return_to(), "/");
+        assert_eq!(state("//attacker.example.com/").return_to(), "/");
+        assert_eq!(state("/\\attacker.example.com/").return_to(), "/");
+        assert_eq!(state("/\t/attacker.example.com/").return_to(), "/");
+        assert_eq!(state("/\n/attacker.example.com/").return_to(), "/");
+    }
+
+    #[test]
+    fn test_validate_issuer() {
+        let state =
+            AuthorizationState::new("csrf".to_string(), Some("https://example.com/".to_string()));
+        assert!(state
+            .validate_issuer(Some("https://example.com/"), true)
+            .is_ok());
+        assert!(state.validate_issuer(None, false).is_ok());
+        assert!(state.validate_issuer(None, true).is_err());
+        assert!(state
+            .validate_issuer(Some("https://attacker.example.com/"), false)
+            .is_err());
+
+        let state = AuthorizationState::new("csrf".to_string(), None);
+        assert!(state
+            .validate_issuer(Some("https://example.com/"), false)
+            .is_err());
+    }
+}
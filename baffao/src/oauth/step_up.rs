@@ -0,0 +1,86 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+
+/**
+ * AuthenticationRequirement
+ *
+ * Authentication level a request needs: one of the `acr_values` and an authentication
+ * performed less than `max_age` seconds ago.
+ * Step-Up Authentication Challenge: https://datatracker.ietf.org/doc/html/rfc9470
+*/
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct AuthenticationRequirement {
+    pub acr_values: Option<Vec<String>>,
+    pub max_age: Option<u64>,
+}
+
+impl AuthenticationRequirement {
+    pub fn is_empty(&self) -> bool {
+        self.acr_values.as_ref().is_none_or(Vec::is_empty) && self.max_age.is_none()
+    }
+
+    pub fn is_satisfied_by(&self, acr: Option<&str>, auth_time: Option<DateTime<Utc>>) -> bool {
+        let acr_satisfied = match &self.acr_values {
+            Some(acr_values) if !acr_values.is_empty() => {
+                acr.is_some_and(|acr| acr_values.iter().any(|value| value == acr))
+            }
+            _ => true,
+        };
+        let max_age_satisfied = match self.max_age {
+            Some(max_age) => auth_time.is_some_and(|auth_time| {
+                Utc::now() - auth_time <= Duration::seconds(max_age as i64)
+            }),
+            None => true,
+        };
+
+        acr_satisfied && max_age_satisfied
+    }
+
+    // Authorization request parameters asking for this level
+    pub fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![];
+        if let Some(acr_values) = self.acr_values.as_ref().filter(|v| !v.is_empty()) {
+            params.push(("acr_values", acr_values.join(" ")));
+        }
+        if let Some(max_age) = self.max_age {
+            params.push(("max_age", max_age.to_string()));
+        }
+        params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_satisfied_by() {
+        let requirement = AuthenticationRequirement {
+            acr_values: Some(vec!["mfa".to_string(), "hwk".to_string()]),
+            max_age: Some(300),
+        };
+        let now = Utc::now();
+
+        assert!(requirement.is_satisfied_by(Some("mfa"), Some(now)));
+        assert!(!requirement.is_satisfied_by(Some("pwd"), Some(now)));
+        assert!(!requirement.is_satisfied_by(None, Some(now)));
+        assert!(!requirement.is_satisfied_by(Some("hwk"), Some(now - Duration::seconds(600))));
+        assert!(!requirement.is_satisfied_by(Some("hwk"), None));
+        assert!(AuthenticationRequirement::default().is_satisfied_by(None, None));
+    }
+
+    #[test]
+    fn test_params() {
+        let requirement = AuthenticationRequirement {
+            acr_values: Some(vec!["mfa".to_string(), "hwk".to_string()]),
+            max_age: Some(300),
+        };
+        assert_eq!(
+            requirement.params(),
+            vec![
+                ("acr_values", "mfa hwk".to_string()),
+                ("max_age", "300".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use std::fs;
+
+use anyhow::{Context, Error};
+use serde::Deserialize;
+
+/**
+ * TlsClientConfig
+ *
+ * Client certificate and key (PEM, PKCS#8) presented to the authorization server, and an
+ * optional CA certificate to trust in addition to the system ones.
+ * Mutual-TLS Client Authentication: https://datatracker.ietf.org/doc/html/rfc8705
+*/
+#[derive(Deserialize, Clone, Debug)]
+pub struct TlsClientConfig {
+    pub certificate: String,
+    pub key: String,
+    pub ca_certificate: Option<String>,
+}
+
+impl TlsClientConfig {
+    pub fn read_identity(&self) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        let certificate = fs::read(&self.certificate)
+            .with_context(|| format!("Failed to read client certificate {}", self.certificate))?;
+        let key = fs::read(&self.key)
+            .with_context(|| format!("Failed to read client key {}", self.key))?;
+
+        Ok((certificate, key))
+    }
+
+    pub fn read_ca_certificate(&self) -> Result<Option<Vec<u8>>, Error> {
+        self.ca_certificate
+            .as_ref()
+            .map(|path| {
+                fs::read(path).with_context(|| format!("Failed to read CA certificate {}", path))
+            })
+            .transpose()
+    }
+}
+
+// Redirects are not followed, a provider endpoint must not send the requests to another host
+pub fn build_http_client(tls: Option<&TlsClientConfig>) -> Result<reqwest::Client, Error> {
+    let mut builder = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none());
+    if let Some(tls) = tls {
+        let (certificate, key) = tls.read_identity()?;
+        builder = builder.identity(
+            reqwest::Identity::from_pkcs8_pem(&certificate, &key)
+                .context("Invalid client certificate")?,
+        );
+        if let Some(ca_certificate) = tls.read_ca_certificate()? {
+            builder = builder.add_root_certificate(
+                reqwest::Certificate::from_pem(&ca_certificate)
+                    .context("Invalid CA certificate")?,
+            );
+        }
+    }
+
+    builder.build().context("Failed to build the HTTP client")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        path::Path,
+        sync::Arc,
+        thread,
+    };
+
+    use openssl::{
+        asn1::Asn1Time,
+        bn::BigNum,
+        hash::MessageDigest,
+        pkey::{PKey, Private},
+        rsa::Rsa,
+        ssl::{SslAcceptor, SslMethod, SslVerifyMode},
+        x509::{
+            extension::{BasicConstraints, ExtendedKeyUsage, KeyUsage, SubjectAlternativeName},
+            X509Builder, X509NameBuilder, X509,
+        },
+    };
+
+    use super::*;
+
+    struct Certificate {
+        certificate: X509,
+        key: PKey<Private>,
+    }
+
+    enum Usage {
+        Ca,
+        Server,
+        Client,
+    }
+
+    fn certificate(serial: u32, issuer: Option<&Certificate>, usage: Usage) -> Certificate {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut subject = X509NameBuilder::new().unwrap();
+        subject
+            .append_entry_by_text("CN", &format!("baffao-{}", serial))
+            .unwrap();
+        let subject = subject.build();
+
+        let mut builder = X509Builder::new().unwrap();
+        builder.set_version(2).unwrap();
+        builder
+            .set_serial_number(&BigNum::from_u32(serial).unwrap().to_asn1_integer().unwrap())
+            .unwrap();
+        builder.set_subject_name(&subject).unwrap();
+        builder
+            .set_issuer_name(issuer.map_or(&subject, |issuer| issuer.certificate.subject_name()))
+            .unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        match usage {
+            Usage::Ca => {
+                builder
+                    .append_extension(BasicConstraints::new().critical().ca().build().unwrap())
+                    .unwrap();
+                builder
+                    .append_extension(KeyUsage::new().key_cert_sign().crl_sign().build().unwrap())
+                    .unwrap();
+            }
+            Usage::Server => {
+                let san = SubjectAlternativeName::new()
+                    .dns("localhost")
+                    .build(&builder.x509v3_context(issuer.map(|issuer| &*issuer.certificate), None))
+                    .unwrap();
+                builder.append_extension(san).unwrap();
+                builder
+                    .append_extension(ExtendedKeyUsage::new().server_auth().build().unwrap())
+                    .unwrap();
+            }
+            Usage::Client => {
+                builder
+                    .append_extension(ExtendedKeyUsage::new().client_auth().build().unwrap())
+                    .unwrap();
+            }
+        }
+        let signing_key = issuer.map_or(&key, |issuer| &issuer.key);
+        builder.sign(signing_key, MessageDigest::sha256()).unwrap();
+
+        Certificate {
+            certificate: builder.build(),
+            key,
+        }
+    }
+
+    fn write(dir: &Path, name: &str, content: Vec<u8>) -> String {
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    // Server answering every TLS connection that presents a client certificate signed by the CA
+    fn serve_mutual_tls(ca: &Certificate, server: &Certificate) -> u16 {
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        acceptor.set_certificate(&server.certificate).unwrap();
+        acceptor.set_private_key(&server.key).unwrap();
+        acceptor
+            .cert_store_mut()
+            .add_cert(ca.certificate.clone())
+            .unwrap();
+        acceptor.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+        let acceptor = Arc::new(acceptor.build());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = acceptor.accept(stream.unwrap()) else {
+                    continue;
+                };
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request);
+                let _ = stream.write_all(
+                    b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok",
+                );
+            }
+        });
+
+        port
+    }
+
+    #[tokio::test]
+    async fn test_build_http_client_presents_client_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        let ca = certificate(1, None, Usage::Ca);
+        let server = certificate(2, Some(&ca), Usage::Server);
+        let client = certificate(3, Some(&ca), Usage::Client);
+        let port = serve_mutual_tls(&ca, &server);
+        let url = format!("https://localhost:{}/token", port);
+        let ca_certificate = ca.certificate.to_pem().unwrap();
+
+        let tls = TlsClientConfig {
+            certificate: write(
+                dir.path(),
+                "client.pem",
+                client.certificate.to_pem().unwrap(),
+            ),
+            key: write(
+                dir.path(),
+                "client.key",
+                client.key.private_key_to_pem_pkcs8().unwrap(),
+            ),
+            ca_certificate: Some(write(dir.path(), "ca.pem", ca_certificate.clone())),
+        };
+        let response = build_http_client(Some(&tls))
+            .unwrap()
+            .get(&url)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+
+        // The server refuses the handshake without a client certificate
+        let without_certificate = reqwest::Client::builder()
+            .add_root_certificate(reqwest::Certificate::from_pem(&ca_certificate).unwrap())
+            .build()
+            .unwrap();
+        assert!(without_certificate.get(&url).send().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_build_http_client_does_not_follow_redirects() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request);
+                let _ = stream.write_all(
+                    b"HTTP/1.1 302 Found\r\nlocation: http://169.254.169.254/\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                );
+            }
+        });
+
+        let response = build_http_client(None)
+            .unwrap()
+            .get(format!("http://127.0.0.1:{}/token", port))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::FOUND);
+    }
+}
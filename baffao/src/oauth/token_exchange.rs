@@ -0,0 +1,95 @@
+use anyhow::{Context, Error};
+use serde::Deserialize;
+
+pub const TOKEN_EXCHANGE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+pub const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+
+/**
+ * TokenExchangeRequest
+ *
+ * Audience, resource and scope of the token the session access token is exchanged for.
+ * Token Exchange: https://datatracker.ietf.org/doc/html/rfc8693
+*/
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct TokenExchangeRequest {
+    pub audience: Option<String>,
+    pub resource: Option<String>,
+    pub scope: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+pub struct TokenExchangeResponse {
+    pub access_token: String,
+    pub issued_token_type: Option<String>,
+    pub token_type: String,
+    pub expires_in: Option<u64>,
+}
+
+impl TokenExchangeRequest {
+    pub fn params(&self, subject_token: &str) -> Vec<(String, String)> {
+        let mut params = vec![
+            (
+                "grant_type".to_string(),
+                TOKEN_EXCHANGE_GRANT_TYPE.to_string(),
+            ),
+            ("subject_token".to_string(), subject_token.to_string()),
+            (
+                "subject_token_type".to_string(),
+                ACCESS_TOKEN_TYPE.to_string(),
+            ),
+            (
+                "requested_token_type".to_string(),
+                ACCESS_TOKEN_TYPE.to_string(),
+            ),
+        ];
+        if let Some(audience) = &self.audience {
+            params.push(("audience".to_string(), audience.clone()));
+        }
+        if let Some(resource) = &self.resource {
+            params.push(("resource".to_string(), resource.clone()));
+        }
+        if let Some(scope) = &self.scope {
+            params.push(("scope".to_string(), scope.join(" ")));
+        }
+        params
+    }
+}
+
+pub fn parse_token_exchange_response(
+    status: reqwest::StatusCode,
+    body: &[u8],
+) -> Result<TokenExchangeResponse, Error> {
+    if !status.is_success() {
+        return Err(Error::msg(format!(
+            "Failed to exchange token: {}",
+            String::from_utf8_lossy(body)
+        )));
+    }
+
+    serde_json::from_slice::<TokenExchangeResponse>(body).context("Invalid token exchange response")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_exchange_params() {
+        let request = TokenExchangeRequest {
+            audience: Some("orders".to_string()),
+            resource: None,
+            scope: Some(vec!["orders:read".to_string(), "orders:write".to_string()]),
+        };
+
+        let params = request.params("token");
+
+        assert!(params.contains(&(
+            "grant_type".to_string(),
+            TOKEN_EXCHANGE_GRANT_TYPE.to_string()
+        )));
+        assert!(params.contains(&("subject_token".to_string(), "token".to_string())));
+        assert!(params.contains(&("audience".to_string(), "orders".to_string())));
+        assert!(params.contains(&("scope".to_string(), "orders:read orders:write".to_string())));
+        assert!(!params.iter().any(|(name, _)| name == "resource"));
+    }
+}
@@ -0,0 +1,77 @@
+use anyhow::Error;
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use serde_json::{Map, Value};
+
+/**
+ * Validate a signed userinfo response, it must contain the iss and aud claims.
+*/
+pub fn validate_userinfo(
+    userinfo: &str,
+    key: &DecodingKey,
+    alg: Algorithm,
+    issuer: &str,
+    client_id: &str,
+    clock_skew: u64,
+) -> Result<Map<String, Value>, Error> {
+    let mut validation = Validation::new(alg);
+    validation.leeway = clock_skew;
+    validation.set_issuer(&[issuer]);
+    validation.set_audience(&[client_id]);
+    validation.set_required_spec_claims(&["iss", "aud", "sub"]);
+
+    let claims = decode::<Map<String, Value>>(userinfo, key, &validation)
+        .map_err(|e| Error::msg(format!("Invalid userinfo response: {}", e)))?
+        .claims;
+
+    Ok(claims)
+}
+
+/**
+ * Merge the userinfo claims over the ID token claims, both must be about the same subject.
+*/
+pub fn merge_userinfo(
+    mut claims: Map<String, Value>,
+    userinfo: Map<String, Value>,
+) -> Result<Map<String, Value>, Error> {
+    if claims.contains_key("sub") && claims.get("sub") != userinfo.get("sub") {
+        return Err(Error::msg("Userinfo subject mismatch"));
+    }
+
+    claims.extend(userinfo);
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn object(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_merge_userinfo() {
+        let claims = object(json!({ "sub": "user", "name": "User", "acr": "1" }));
+        let userinfo =
+            object(json!({ "sub": "user", "name": "Jane", "email": "jane@example.com" }));
+
+        let merged = merge_userinfo(claims, userinfo).unwrap();
+
+        assert_eq!(
+            Value::Object(merged),
+            json!({ "sub": "user", "name": "Jane", "acr": "1", "email": "jane@example.com" })
+        );
+    }
+
+    #[test]
+    fn test_merge_userinfo_rejects_other_subject() {
+        let claims = object(json!({ "sub": "user" }));
+        let userinfo = object(json!({ "sub": "other" }));
+
+        let error = merge_userinfo(claims, userinfo).unwrap_err();
+
+        assert_eq!(error.to_string(), "Userinfo subject mismatch");
+    }
+}
@@ -0,0 +1,75 @@
+use serde_json::{Map, Value};
+
+use crate::settings::SessionConfig;
+
+/**
+ * Select the identity claims kept in the session.
+ * Claims are looked up under their mapped name when one is configured, nested claims can be
+ * reached with a dotted path (e.g. `realm_access.roles`).
+*/
+pub fn map_claims(source: &Map<String, Value>, config: &SessionConfig) -> Map<String, Value> {
+    let names = config
+        .claims
+        .iter()
+        .chain(config.claims_mapping.keys())
+        .collect::<Vec<_>>();
+
+    let mut claims = Map::new();
+    for name in names {
+        let path = config.claims_mapping.get(name).unwrap_or(name);
+        if let Some(value) = lookup(source, path) {
+            claims.insert(name.to_string(), value.clone());
+        }
+    }
+
+    claims
+}
+
+fn lookup<'a>(source: &'a Map<String, Value>, path: &str) -> Option<&'a Value> {
+    // Claims whose name contains a dot are matched before walking the path
+    if let Some(value) = source.get(path) {
+        return Some(value);
+    }
+
+    let mut segments = path.split('.');
+    let mut value = source.get(segments.next()?)?;
+    for segment in segments {
+        value = value.as_object()?.get(segment)?;
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_map_claims() {
+        let source = json!({
+            "sub": "user",
+            "email": "user@example.com",
+            "address": { "country": "FR" },
+            "realm_access": { "roles": ["admin"] },
+        });
+        let config = SessionConfig {
+            claims: vec!["sub".to_string(), "name".to_string(), "roles".to_string()],
+            claims_mapping: HashMap::from([
+                ("roles".to_string(), "realm_access.roles".to_string()),
+                ("country".to_string(), "address.country".to_string()),
+            ]),
+            exposed_claims: vec![],
+            lifetime: None,
+        };
+
+        let claims = map_claims(source.as_object().unwrap(), &config);
+
+        assert_eq!(
+            Value::Object(claims),
+            json!({ "sub": "user", "roles": ["admin"], "country": "FR" })
+        );
+    }
+}
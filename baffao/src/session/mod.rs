@@ -1,19 +1,66 @@
 use anyhow::Error;
+pub use claims::map_claims;
 pub use extract_session::extract_session;
+pub use store::{MemorySessionStore, SessionStore};
 pub use update_session::update_session;
 
+mod claims;
 mod extract_session;
+mod store;
 mod update_session;
 
 use base64::{engine::general_purpose::STANDARD, Engine as _};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/**
+ * ResourceToken
+ *
+ * Access token restricted to a resource server, `dpop` when it is bound to the DPoP key of the
+ * session.
+ * Resource Indicators: https://datatracker.ietf.org/doc/html/rfc8707
+*/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ResourceToken {
+    pub access_token: String,
+    pub exp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dpop: bool,
+}
+
+impl ResourceToken {
+    pub fn is_expired(&self) -> bool {
+        self.exp < Utc::now()
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Session {
     id: String,
     iat: DateTime<Utc>,
     exp: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sub: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    acr: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    auth_time: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scopes: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    claims: Map<String, Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dpop_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    provider: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    resource_tokens: HashMap<String, ResourceToken>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    exchanged_tokens: HashMap<String, ResourceToken>,
 }
 
 impl Session {
@@ -22,9 +69,76 @@ impl Session {
             id: id.unwrap_or_else(generate_session_id),
             iat: iat.unwrap_or_else(Utc::now),
             exp,
+            sub: None,
+            sid: None,
+            acr: None,
+            auth_time: None,
+            scopes: None,
+            claims: Map::new(),
+            dpop_key: None,
+            provider: None,
+            resource_tokens: HashMap::new(),
+            exchanged_tokens: HashMap::new(),
         }
     }
 
+    pub fn with_expire(mut self, exp: Option<DateTime<Utc>>) -> Self {
+        self.exp = exp;
+        self
+    }
+
+    // Subject and provider session id, used to find the sessions to terminate on logout
+    pub fn with_identity(mut self, sub: Option<String>, sid: Option<String>) -> Self {
+        self.sub = sub;
+        self.sid = sid;
+        self
+    }
+
+    // Authentication context class and time of the ID token, checked by the step-up routes
+    pub fn with_authentication(
+        mut self,
+        acr: Option<String>,
+        auth_time: Option<DateTime<Utc>>,
+    ) -> Self {
+        self.acr = acr;
+        self.auth_time = auth_time;
+        self
+    }
+
+    pub fn with_scopes(mut self, scopes: Option<Vec<String>>) -> Self {
+        self.scopes = scopes;
+        self
+    }
+
+    pub fn with_claims(mut self, claims: Map<String, Value>) -> Self {
+        self.claims = claims;
+        self
+    }
+
+    // Private DPoP key the access tokens are bound to, it is never sent to the browser
+    pub fn with_dpop_key(mut self, dpop_key: Option<String>) -> Self {
+        self.dpop_key = dpop_key;
+        self
+    }
+
+    // Name of the provider the session was created with, none for the default provider
+    pub fn with_provider(mut self, provider: Option<String>) -> Self {
+        self.provider = provider;
+        self
+    }
+
+    // Access tokens obtained for a given resource, they are never sent to the browser
+    pub fn with_resource_token(mut self, resource: String, token: ResourceToken) -> Self {
+        self.resource_tokens.insert(resource, token);
+        self
+    }
+
+    // Tokens obtained by token exchange for an upstream, they are never sent to the browser
+    pub fn with_exchanged_token(mut self, upstream: String, token: ResourceToken) -> Self {
+        self.exchanged_tokens.insert(upstream, token);
+        self
+    }
+
     pub fn id(&self) -> &str {
         &self.id
     }
@@ -37,6 +151,54 @@ impl Session {
         self.exp
     }
 
+    pub fn sub(&self) -> Option<&str> {
+        self.sub.as_deref()
+    }
+
+    pub fn sid(&self) -> Option<&str> {
+        self.sid.as_deref()
+    }
+
+    pub fn acr(&self) -> Option<&str> {
+        self.acr.as_deref()
+    }
+
+    pub fn auth_time(&self) -> Option<DateTime<Utc>> {
+        self.auth_time
+    }
+
+    pub fn scopes(&self) -> Option<&Vec<String>> {
+        self.scopes.as_ref()
+    }
+
+    pub fn claims(&self) -> &Map<String, Value> {
+        &self.claims
+    }
+
+    pub fn dpop_key(&self) -> Option<&str> {
+        self.dpop_key.as_deref()
+    }
+
+    pub fn provider(&self) -> Option<&str> {
+        self.provider.as_deref()
+    }
+
+    pub fn resource_token(&self, resource: &str) -> Option<&ResourceToken> {
+        self.resource_tokens.get(resource)
+    }
+
+    pub fn exchanged_token(&self, upstream: &str) -> Option<&ResourceToken> {
+        self.exchanged_tokens.get(upstream)
+    }
+
+    pub fn exposed_claims(&self, allowlist: &[String]) -> Map<String, Value> {
+        self.claims
+            .iter()
+            .filter(|(name, _)| allowlist.contains(name))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+
     pub fn is_expired(&self) -> bool {
         match self.exp {
             Some(exp) => exp < Utc::now(),
@@ -53,7 +215,13 @@ impl Session {
     }
 
     pub fn encode_cookie(&self) -> String {
-        STANDARD.encode(self.encode().as_bytes())
+        let session = Self {
+            dpop_key: None,
+            resource_tokens: HashMap::new(),
+            exchanged_tokens: HashMap::new(),
+            ..self.clone()
+        };
+        STANDARD.encode(session.encode().as_bytes())
     }
 
     pub fn decode_cookie(encoded: String) -> Result<Self, Error> {
@@ -0,0 +1,150 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use async_trait::async_trait;
+
+use super::Session;
+
+/**
+ * SessionStore
+ *
+ * Server-side registry of the active sessions. A session cookie is only honoured while its
+ * session is in the store, which allows to terminate sessions without the browser, e.g. on
+ * back-channel logout. The logouts of a provider only remove the sessions created with it,
+ * `None` being the default provider. The methods are async so that the store can be backed by a database.
+ * Expired sessions are not removed on insert, `remove_expired` must be called periodically. A swept
+ * session is told apart from a terminated one by the expiry of its cookie, so that its refresh
+ * token can still start a new session.
+*/
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    async fn get(&self, id: &str) -> Option<Session>;
+    async fn insert(&self, session: Session);
+    async fn remove(&self, id: &str) -> Option<Session>;
+    async fn remove_by_sub(&self, provider: Option<&str>, sub: &str) -> Vec<Session>;
+    async fn remove_by_sid(&self, provider: Option<&str>, sid: &str) -> Vec<Session>;
+    async fn remove_expired(&self) -> Vec<Session>;
+}
+
+/**
+ * MemorySessionStore
+ *
+ * Keeps the sessions in memory, they are lost when the process restarts.
+*/
+#[derive(Default)]
+pub struct MemorySessionStore {
+    sessions: RwLock<HashMap<String, Session>>,
+}
+
+impl MemorySessionStore {
+    fn remove_matching(&self, predicate: impl Fn(&Session) -> bool) -> Vec<Session> {
+        let mut sessions = self.sessions.write().unwrap();
+        let ids = sessions
+            .values()
+            .filter(|session| predicate(session))
+            .map(|session| session.id().to_string())
+            .collect::<Vec<_>>();
+
+        ids.iter().filter_map(|id| sessions.remove(id)).collect()
+    }
+}
+
+#[async_trait]
+impl SessionStore for MemorySessionStore {
+    async fn get(&self, id: &str) -> Option<Session> {
+        self.sessions.read().unwrap().get(id).cloned()
+    }
+
+    async fn insert(&self, session: Session) {
+        self.sessions
+            .write()
+            .unwrap()
+            .insert(session.id().to_string(), session);
+    }
+
+    async fn remove(&self, id: &str) -> Option<Session> {
+        self.sessions.write().unwrap().remove(id)
+    }
+
+    async fn remove_by_sub(&self, provider: Option<&str>, sub: &str) -> Vec<Session> {
+        self.remove_matching(|session| session.provider() == provider && session.sub() == Some(sub))
+    }
+
+    async fn remove_by_sid(&self, provider: Option<&str>, sid: &str) -> Vec<Session> {
+        self.remove_matching(|session| session.provider() == provider && session.sid() == Some(sid))
+    }
+
+    async fn remove_expired(&self) -> Vec<Session> {
+        self.remove_matching(Session::is_expired)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::*;
+
+    fn session(sub: &str, sid: &str) -> Session {
+        Session::new(None, None, None).with_identity(Some(sub.to_string()), Some(sid.to_string()))
+    }
+
+    #[tokio::test]
+    async fn test_memory_session_store() {
+        let store = MemorySessionStore::default();
+        let session = session("user", "sid");
+
+        store.insert(session.clone()).await;
+        assert!(store.get(session.id()).await.is_some());
+
+        store.remove(session.id()).await;
+        assert!(store.get(session.id()).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_memory_session_store_remove_by_sub_and_sid() {
+        let store = MemorySessionStore::default();
+        let first = session("user", "first");
+        let second = session("user", "second");
+        let other = session("other", "third");
+        store.insert(first.clone()).await;
+        store.insert(second.clone()).await;
+        store.insert(other.clone()).await;
+
+        assert_eq!(store.remove_by_sid(None, "first").await.len(), 1);
+        assert!(store.get(first.id()).await.is_none());
+        assert!(store.get(second.id()).await.is_some());
+
+        assert_eq!(store.remove_by_sub(None, "user").await.len(), 1);
+        assert!(store.get(second.id()).await.is_none());
+        assert!(store.get(other.id()).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_memory_session_store_remove_by_provider() {
+        let store = MemorySessionStore::default();
+        let default = session("user", "sid");
+        let other = session("user", "sid").with_provider(Some("other".to_string()));
+        store.insert(default.clone()).await;
+        store.insert(other.clone()).await;
+
+        assert_eq!(store.remove_by_sid(Some("other"), "sid").await.len(), 1);
+        assert!(store.get(default.id()).await.is_some());
+        assert!(store.get(other.id()).await.is_none());
+
+        assert_eq!(store.remove_by_sub(Some("other"), "user").await.len(), 0);
+        assert_eq!(store.remove_by_sub(None, "user").await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_memory_session_store_remove_expired() {
+        let store = MemorySessionStore::default();
+        let expired = session("user", "expired").with_expire(Some(Utc::now() - Duration::hours(1)));
+        let active = session("user", "active");
+        store.insert(expired.clone()).await;
+        store.insert(active.clone()).await;
+
+        assert_eq!(store.remove_expired().await.len(), 1);
+        assert!(store.get(expired.id()).await.is_none());
+        assert!(store.get(active.id()).await.is_some());
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use axum_extra::extract::cookie::SameSite;
 use serde::Deserialize;
 
@@ -7,9 +9,27 @@ pub struct ServerConfig {
     pub port: u16,
     pub base_url: String,
     pub cookies: CookiesConfig,
+    // No session claims are kept nor exposed when the section is missing
+    #[serde(default)]
+    pub session: SessionConfig,
     pub error_url: String,
 }
 
+#[derive(Deserialize, Clone, Default)]
+pub struct SessionConfig {
+    // Identity claims kept in the session
+    #[serde(default)]
+    pub claims: Vec<String>,
+    // Session claim name to the (dotted) path of the claim in the ID token or userinfo
+    #[serde(default)]
+    pub claims_mapping: HashMap<String, String>,
+    // Session claims the browser is allowed to see
+    #[serde(default)]
+    pub exposed_claims: Vec<String>,
+    // Lifetime of the session in seconds, independent of the access token expiry
+    pub lifetime: Option<u64>,
+}
+
 #[derive(Deserialize, Clone)]
 pub struct JwtConfig {
     pub secret: String,
@@ -62,7 +82,9 @@ impl CookieConfig {
 pub struct CookiesConfig {
     pub oauth_csrf: CookieConfig,
     pub oauth_pkce: CookieConfig,
+    pub oauth_nonce: CookieConfig,
     pub access_token: CookieConfig,
     pub refresh_token: CookieConfig,
+    pub id_token: CookieConfig,
     pub session: CookieConfig,
 }
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use axum::{routing::post, Form, Json, Router};
+use axum_extra::extract::{cookie::SameSite, CookieJar};
+use baffao::{
+    error::AuthorizationError,
+    handlers::{proxy, UpstreamToken},
+    oauth::{OAuthConfig, OAuthHttpHandler},
+    settings::{CookieConfig, CookiesConfig, SessionConfig},
+};
+use http::Method;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+
+async fn token(Form(params): Form<HashMap<String, String>>) -> Json<Value> {
+    let access_token = match params.get("resource") {
+        Some(resource) => format!("{}-token", resource),
+        None => "token".to_string(),
+    };
+
+    Json(json!({
+        "access_token": access_token,
+        "token_type": "Bearer",
+        "expires_in": 300,
+        "refresh_token": "refresh",
+    }))
+}
+
+// Only the session token is active
+async fn introspect(Form(params): Form<HashMap<String, String>>) -> Json<Value> {
+    Json(json!({
+        "active": params.get("token").map(String::as_str) == Some("token"),
+    }))
+}
+
+async fn serve_provider() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let router = Router::new()
+            .route("/token", post(token))
+            .route("/introspect", post(introspect));
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    format!("http://{}", addr)
+}
+
+fn cookie(name: &str) -> CookieConfig {
+    CookieConfig {
+        name: name.to_string(),
+        domain: "localhost".to_string(),
+        secure: false,
+        http_only: true,
+        same_site: SameSite::Lax,
+    }
+}
+
+async fn handler() -> OAuthHttpHandler {
+    let provider = serve_provider().await;
+    let oauth_config: OAuthConfig = serde_json::from_value(json!({
+        "client_id": "client",
+        "client_secret": "secret",
+        "authorization_redirect_uri": "http://localhost/oauth/callback",
+        "authorization_endpoint": "http://localhost/authorize",
+        "token_endpoint": format!("{}/token", provider),
+        "introspection_endpoint": format!("{}/introspect", provider),
+        "introspection": true,
+        "fetch_userinfo": false,
+    }))
+    .unwrap();
+    let cookies_config = CookiesConfig {
+        oauth_csrf: cookie("csrf"),
+        oauth_pkce: cookie("pkce"),
+        oauth_nonce: cookie("nonce"),
+        access_token: cookie("access_token"),
+        refresh_token: cookie("refresh_token"),
+        id_token: cookie("id_token"),
+        session: cookie("session"),
+    };
+
+    OAuthHttpHandler::new(oauth_config, cookies_config, SessionConfig::default())
+        .await
+        .unwrap()
+}
+
+async fn login(handler: &OAuthHttpHandler) -> CookieJar {
+    handler
+        .exchange_code(
+            CookieJar::new(),
+            "code".to_string(),
+            "verifier".to_string(),
+            "nonce".to_string(),
+            None,
+        )
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_proxy_inactive_resource_token() {
+    let handler = handler().await;
+    let jar = login(&handler).await;
+
+    let result = proxy(
+        handler.clone(),
+        jar.clone(),
+        &Method::GET,
+        "http://localhost/api",
+        UpstreamToken::Session,
+        None,
+        None,
+    )
+    .await;
+    assert!(result.is_ok());
+
+    let error = proxy(
+        handler,
+        jar,
+        &Method::GET,
+        "http://localhost/api",
+        UpstreamToken::Resource("api"),
+        None,
+        None,
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<AuthorizationError>(),
+        Some(AuthorizationError::InvalidToken(_))
+    ));
+}
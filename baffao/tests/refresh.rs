@@ -0,0 +1,163 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{routing::post, Form, Json, Router};
+use axum_extra::extract::{cookie::SameSite, CookieJar};
+use baffao::{
+    oauth::{OAuthConfig, OAuthHttpHandler},
+    session::{MemorySessionStore, SessionStore},
+    settings::{CookieConfig, CookiesConfig, SessionConfig},
+};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+
+// Token endpoint issuing access tokens that are already expired
+async fn token(Form(params): Form<HashMap<String, String>>) -> Json<Value> {
+    let (access_token, refresh_token) = match params.get("grant_type").map(String::as_str) {
+        Some("refresh_token") => ("refreshed", "rotated"),
+        _ => ("initial", "refresh"),
+    };
+
+    Json(json!({
+        "access_token": access_token,
+        "token_type": "Bearer",
+        "expires_in": 0,
+        "refresh_token": refresh_token,
+    }))
+}
+
+async fn serve_token_endpoint() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, Router::new().route("/token", post(token)))
+            .await
+            .unwrap();
+    });
+
+    format!("http://{}/token", addr)
+}
+
+fn cookie(name: &str) -> CookieConfig {
+    CookieConfig {
+        name: name.to_string(),
+        domain: "localhost".to_string(),
+        secure: false,
+        http_only: true,
+        same_site: SameSite::Lax,
+    }
+}
+
+async fn handler(session_lifetime: Option<u64>) -> OAuthHttpHandler {
+    let oauth_config: OAuthConfig = serde_json::from_value(json!({
+        "client_id": "client",
+        "client_secret": "secret",
+        "authorization_redirect_uri": "http://localhost/oauth/callback",
+        "authorization_endpoint": "http://localhost/authorize",
+        "token_endpoint": serve_token_endpoint().await,
+        "fetch_userinfo": false,
+    }))
+    .unwrap();
+    let cookies_config = CookiesConfig {
+        oauth_csrf: cookie("csrf"),
+        oauth_pkce: cookie("pkce"),
+        oauth_nonce: cookie("nonce"),
+        access_token: cookie("access_token"),
+        refresh_token: cookie("refresh_token"),
+        id_token: cookie("id_token"),
+        session: cookie("session"),
+    };
+    let session_config = SessionConfig {
+        lifetime: session_lifetime,
+        ..Default::default()
+    };
+
+    OAuthHttpHandler::new(oauth_config, cookies_config, session_config)
+        .await
+        .unwrap()
+}
+
+async fn login(handler: &OAuthHttpHandler) -> CookieJar {
+    handler
+        .exchange_code(
+            CookieJar::new(),
+            "code".to_string(),
+            "verifier".to_string(),
+            "nonce".to_string(),
+            None,
+        )
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_refresh_expired_access_token() {
+    let handler = handler(None).await;
+    let jar = login(&handler).await;
+
+    let (jar, access_token) = handler.refresh_access_token(jar).await.unwrap();
+    assert_eq!(access_token.as_deref(), Some("refreshed"));
+    assert_eq!(jar.get("refresh_token").unwrap().value(), "rotated");
+    let (_, session) = handler.get_session(jar).await;
+    assert!(session.is_some());
+}
+
+#[tokio::test]
+async fn test_refresh_expired_session() {
+    let session_store = Arc::new(MemorySessionStore::default());
+    let handler = handler(Some(0))
+        .await
+        .with_session_store(session_store.clone());
+    let jar = login(&handler).await;
+    let expired_session = handler.find_session(&jar).await.unwrap();
+    let (jar, session) = handler.get_session(jar).await;
+    assert!(session.is_none());
+
+    let (jar, access_token) = handler.refresh_access_token(jar).await.unwrap();
+    assert_eq!(access_token.as_deref(), Some("refreshed"));
+    let session = handler.find_session(&jar).await.unwrap();
+    assert_ne!(session.id(), expired_session.id());
+    assert!(session_store.get(expired_session.id()).await.is_none());
+}
+
+#[tokio::test]
+async fn test_refresh_swept_session() {
+    let session_store = Arc::new(MemorySessionStore::default());
+    let handler = handler(Some(0))
+        .await
+        .with_session_store(session_store.clone());
+    let jar = login(&handler).await;
+    assert_eq!(session_store.remove_expired().await.len(), 1);
+
+    let (jar, session) = handler.get_session(jar).await;
+    assert!(session.is_none());
+    assert!(jar.get("refresh_token").is_some());
+
+    let (jar, access_token) = handler.refresh_access_token(jar).await.unwrap();
+    assert_eq!(access_token.as_deref(), Some("refreshed"));
+    assert!(jar.get("session").is_some());
+}
+
+#[tokio::test]
+async fn test_login_replaces_session() {
+    let session_store = Arc::new(MemorySessionStore::default());
+    let handler = handler(None)
+        .await
+        .with_session_store(session_store.clone());
+    let jar = login(&handler).await;
+    let (jar, previous) = handler.get_session(jar).await;
+    let previous = previous.unwrap();
+
+    let jar = handler
+        .exchange_code(
+            jar,
+            "code".to_string(),
+            "verifier".to_string(),
+            "nonce".to_string(),
+            None,
+        )
+        .await
+        .unwrap();
+    assert!(session_store.get(previous.id()).await.is_none());
+    let (_, session) = handler.get_session(jar).await;
+    assert!(session.is_some());
+}